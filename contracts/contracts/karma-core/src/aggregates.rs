@@ -0,0 +1,441 @@
+use cosmwasm_std::{Decimal, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+use crate::algorithm::{
+    KarmaAlgorithm, RatingStats, HIGH_KARMA_RATER_THRESHOLD, HIGH_RATING_THRESHOLD,
+    LOW_KARMA_RATER_THRESHOLD, POOR_RATING_THRESHOLD, VERY_POOR_RATING_THRESHOLD,
+};
+use crate::error::ContractError;
+use crate::karma::half_life_decay;
+use crate::state::{
+    ratings, RatingAggregates, StoredRating, AGGREGATE_MIGRATION, CATEGORY_AGGREGATES,
+    CATEGORY_RATER_COUNTS, KARMA_SCORES, MIGRATING_AGGREGATES, RATER_COUNTS, RATING_AGGREGATES,
+};
+use crate::trust::{rating_satisfaction, record_trust_edge};
+use agent_karma_contracts::types::Rating;
+
+/// Recency half-life: a rating this old (100 days, in seconds) carries half weight
+pub const RECENCY_HALF_LIFE: u64 = 100 * 24 * 60 * 60;
+
+/// Window for counting recent activity (30 days in seconds)
+pub const RECENT_ACTIVITY_WINDOW: u64 = 30 * 24 * 60 * 60;
+
+/// Seconds per recent-activity bucket
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Smoothing factor of the moving average used for trend detection (percent)
+const MOVING_AVERAGE_ALPHA: u64 = 25;
+
+/// Minimum number of ratings before a trend is reported
+const MIN_RATINGS_FOR_TREND: u64 = 10;
+
+/// Fold a new rating into the rated agent's aggregates
+///
+/// Must be called before the rating itself is saved, so that agents rated
+/// before aggregates existed are told apart from agents never rated.
+pub fn record_rating(
+    storage: &mut dyn Storage,
+    rating: &Rating,
+    rater_karma: Uint128,
+) -> Result<(), ContractError> {
+    let agent = rating.rated_address.as_str();
+    let rater = rating.rater_address.as_str();
+
+    let mut aggregates = load_rating_aggregates(storage, agent)?;
+
+    let rater_count = RATER_COUNTS.may_load(storage, (agent, rater))?.unwrap_or(0);
+    RATER_COUNTS.save(storage, (agent, rater), &(rater_count + 1))?;
+    record_trust_edge(storage, agent, rater, rating_satisfaction(rating.score))?;
+
+    fold_rating(&mut aggregates, rating.score, rating.timestamp, rater_count == 0, rater_karma)?;
+    RATING_AGGREGATES.save(storage, agent, &aggregates)?;
+
+    Ok(())
+}
+
+/// Fold a new rating into the rated agent's aggregates for one interaction type
+///
+/// Ratings predating interaction types were never attributed to one, so
+/// there is nothing to rebuild here.
+pub fn record_category_rating(
+    storage: &mut dyn Storage,
+    rating: &Rating,
+    interaction_type: &str,
+    rater_karma: Uint128,
+) -> Result<RatingAggregates, ContractError> {
+    let agent = rating.rated_address.as_str();
+    let rater = rating.rater_address.as_str();
+
+    let mut aggregates = CATEGORY_AGGREGATES
+        .may_load(storage, (agent, interaction_type))?
+        .unwrap_or_default();
+
+    let rater_key = (agent, interaction_type, rater);
+    let rater_count = CATEGORY_RATER_COUNTS.may_load(storage, rater_key)?.unwrap_or(0);
+    CATEGORY_RATER_COUNTS.save(storage, rater_key, &(rater_count + 1))?;
+
+    fold_rating(&mut aggregates, rating.score, rating.timestamp, rater_count == 0, rater_karma)?;
+    CATEGORY_AGGREGATES.save(storage, (agent, interaction_type), &aggregates)?;
+
+    Ok(aggregates)
+}
+
+/// Push an agent's latest rating time forward by `seconds`, overall and in
+/// every interaction type, so that decay measured from it skips that time
+pub fn defer_decay(storage: &mut dyn Storage, agent: &str, seconds: u64) -> Result<(), ContractError> {
+    let mut aggregates = load_rating_aggregates(storage, agent)?;
+    aggregates.last_rating_at = aggregates.last_rating_at.plus_seconds(seconds);
+    RATING_AGGREGATES.save(storage, agent, &aggregates)?;
+
+    let categories = CATEGORY_AGGREGATES
+        .prefix(agent)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (interaction_type, mut aggregates) in categories {
+        aggregates.last_rating_at = aggregates.last_rating_at.plus_seconds(seconds);
+        CATEGORY_AGGREGATES.save(storage, (agent, interaction_type.as_str()), &aggregates)?;
+    }
+
+    Ok(())
+}
+
+/// Load an agent's rating aggregates
+///
+/// Agents rated before aggregates existed must be brought over by
+/// `migrate_rating_aggregates` first: rebuilding them from their whole rating
+/// history could cost more gas than a transaction allows.
+pub fn load_rating_aggregates(storage: &dyn Storage, agent: &str) -> Result<RatingAggregates, ContractError> {
+    if let Some(aggregates) = RATING_AGGREGATES.may_load(storage, agent)? {
+        return Ok(aggregates);
+    }
+
+    let rated = ratings()
+        .idx
+        .rated_agent
+        .prefix(agent.to_string())
+        .keys_raw(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    if rated {
+        return Err(ContractError::RatingAggregatesPending {
+            agent: agent.to_string(),
+        });
+    }
+
+    Ok(RatingAggregates::default())
+}
+
+/// Fold up to `limit` more ratings given before aggregates existed into their
+/// rated agent's aggregates, then publish up to `limit` agents' finished
+/// aggregates; returns the number of ratings folded and whether the migration
+/// is complete
+///
+/// Ratings are folded in time order across agents, resuming after the last
+/// one reached, so an agent's aggregates are only published once all of its
+/// ratings are in. Agents that already have aggregates are skipped.
+pub fn migrate_rating_aggregates(storage: &mut dyn Storage, limit: u32) -> Result<(u32, bool), ContractError> {
+    let limit = limit as usize;
+
+    let start_after = AGGREGATE_MIGRATION.may_load(storage)?;
+    let mut batch = ratings()
+        .idx
+        .timestamp
+        .range(storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let reached_all = batch.len() <= limit;
+    batch.truncate(limit);
+
+    let mut folded = 0;
+    for (rating_id, stored) in batch {
+        let rating = stored.rating;
+        AGGREGATE_MIGRATION.save(storage, &(rating.timestamp.seconds(), rating_id))?;
+
+        let agent = rating.rated_address.as_str();
+        let rater = rating.rater_address.as_str();
+        if RATING_AGGREGATES.has(storage, agent) {
+            continue;
+        }
+        let rater_karma = match stored.rater_karma {
+            Some(rater_karma) => rater_karma,
+            None => KARMA_SCORES.may_load(storage, rater)?.map(|k| k.current_score).unwrap_or_default(),
+        };
+
+        let mut aggregates = MIGRATING_AGGREGATES.may_load(storage, agent)?.unwrap_or_default();
+        let rater_count = RATER_COUNTS.may_load(storage, (agent, rater))?.unwrap_or(0);
+        RATER_COUNTS.save(storage, (agent, rater), &(rater_count + 1))?;
+        record_trust_edge(storage, agent, rater, rating_satisfaction(rating.score))?;
+
+        fold_rating(&mut aggregates, rating.score, rating.timestamp, rater_count == 0, rater_karma)?;
+        MIGRATING_AGGREGATES.save(storage, agent, &aggregates)?;
+        folded += 1;
+    }
+
+    if !reached_all {
+        return Ok((folded, false));
+    }
+
+    let mut finished = MIGRATING_AGGREGATES
+        .range(storage, None, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let published_all = finished.len() <= limit;
+    finished.truncate(limit);
+    for (agent, aggregates) in finished {
+        RATING_AGGREGATES.save(storage, &agent, &aggregates)?;
+        MIGRATING_AGGREGATES.remove(storage, &agent);
+    }
+
+    Ok((folded, published_all))
+}
+
+/// Update the rated agent's aggregates after one of its ratings was amended
+/// to `revised`, or retracted
+///
+/// The old rating is taken out of the aggregates and the revised one put in
+/// its place, so the cost does not grow with the agent's rating count. Agents
+/// rated before aggregates existed must be migrated first. Returns the
+/// aggregates of the rating's interaction type, `None` when it has none or no
+/// ratings are left in it.
+pub fn revise_rating(
+    storage: &mut dyn Storage,
+    previous: &StoredRating,
+    revised: Option<&Rating>,
+) -> Result<Option<RatingAggregates>, ContractError> {
+    let agent = previous.rating.rated_address.as_str();
+    let rater = previous.rating.rater_address.as_str();
+
+    let satisfaction = revised.map(|rating| rating_satisfaction(rating.score)).unwrap_or(0);
+    record_trust_edge(storage, agent, rater, satisfaction - rating_satisfaction(previous.rating.score))?;
+
+    let revised_score = revised.map(|rating| rating.score);
+    let rater_karma = match previous.rater_karma {
+        Some(rater_karma) => rater_karma,
+        None => KARMA_SCORES.may_load(storage, rater)?.map(|k| k.current_score).unwrap_or_default(),
+    };
+
+    let rater_gone = revised.is_none() && !decrement_count(storage, &RATER_COUNTS, (agent, rater))?;
+    let mut aggregates = RATING_AGGREGATES.may_load(storage, agent)?.ok_or_else(|| {
+        ContractError::RatingAggregatesPending {
+            agent: agent.to_string(),
+        }
+    })?;
+    unfold_rating(&mut aggregates, &previous.rating, revised_score, rater_gone, rater_karma)?;
+    RATING_AGGREGATES.save(storage, agent, &aggregates)?;
+
+    let interaction_type = match previous.interaction_type.as_deref() {
+        Some(interaction_type) => interaction_type,
+        None => return Ok(None),
+    };
+    let rater_key = (agent, interaction_type, rater);
+    let rater_gone = revised.is_none() && !decrement_count(storage, &CATEGORY_RATER_COUNTS, rater_key)?;
+    let mut aggregates = CATEGORY_AGGREGATES
+        .may_load(storage, (agent, interaction_type))?
+        .unwrap_or_default();
+    unfold_rating(&mut aggregates, &previous.rating, revised_score, rater_gone, rater_karma)?;
+    if aggregates.histogram.iter().all(|count| *count == 0) {
+        CATEGORY_AGGREGATES.remove(storage, (agent, interaction_type));
+        return Ok(None);
+    }
+    CATEGORY_AGGREGATES.save(storage, (agent, interaction_type), &aggregates)?;
+
+    Ok(Some(aggregates))
+}
+
+/// Count one rating less under a rater count key, returning whether any are
+/// left
+fn decrement_count<'a, K: cw_storage_plus::PrimaryKey<'a>>(
+    storage: &mut dyn Storage,
+    counts: &cw_storage_plus::Map<'a, K, u64>,
+    key: K,
+) -> StdResult<bool> {
+    match counts.may_load(storage, key.clone())? {
+        Some(count) if count > 1 => {
+            counts.save(storage, key, &(count - 1))?;
+            Ok(true)
+        }
+        _ => {
+            counts.remove(storage, key);
+            Ok(false)
+        }
+    }
+}
+
+/// Summarise aggregates into the statistics an algorithm scores from, in O(1)
+pub fn rating_stats(
+    aggregates: &RatingAggregates,
+    algorithm: &dyn KarmaAlgorithm,
+    now: Timestamp,
+) -> Result<RatingStats, ContractError> {
+    let histogram = &aggregates.histogram;
+    let rating_count: u64 = histogram.iter().sum();
+
+    // Recency weights share a common decay factor up to `now`, which cancels
+    // out of the weighted average, so they are used as stored
+    let mut total_weighted_score = Decimal::zero();
+    let mut total_weight = Decimal::zero();
+    let mut score_sum = 0u64;
+    for (index, recency_weight) in aggregates.recency_weights.iter().enumerate() {
+        let score = index as u8 + 1;
+        let weight = recency_weight.checked_mul(algorithm.rating_weight(score))?;
+        total_weighted_score += Decimal::from_ratio(score, 1u8).checked_mul(weight)?;
+        total_weight += weight;
+        score_sum += score as u64 * histogram[index];
+    }
+
+    let weighted_average = if total_weight.is_zero() {
+        Decimal::zero()
+    } else {
+        total_weighted_score.checked_div(total_weight).map_err(|e| ContractError::KarmaCalculationFailed {
+            reason: e.to_string(),
+        })?
+    };
+
+    let window_start = now.seconds().saturating_sub(RECENT_ACTIVITY_WINDOW);
+    let recent_count = aggregates
+        .recent_days
+        .iter()
+        .filter(|(day, _)| (day + 1) * SECONDS_PER_DAY > window_start)
+        .map(|(_, count)| count)
+        .sum();
+
+    let trend = if rating_count >= MIN_RATINGS_FOR_TREND {
+        Some((Decimal::from_ratio(score_sum, rating_count), aggregates.moving_average))
+    } else {
+        None
+    };
+
+    Ok(RatingStats {
+        rating_count,
+        weighted_average,
+        recent_count,
+        unique_raters: aggregates.unique_raters,
+        high_ratings: histogram[(HIGH_RATING_THRESHOLD - 1) as usize..].iter().sum(),
+        poor_ratings: histogram[..(POOR_RATING_THRESHOLD - 1) as usize].iter().sum(),
+        very_poor_ratings: histogram[..VERY_POOR_RATING_THRESHOLD as usize].iter().sum(),
+        trend,
+        high_karma_raters: aggregates.high_karma_raters,
+        low_karma_raters: aggregates.low_karma_raters,
+    })
+}
+
+/// Add a single rating to the aggregates
+fn fold_rating(
+    aggregates: &mut RatingAggregates,
+    score: u8,
+    timestamp: Timestamp,
+    new_rater: bool,
+    rater_karma: Uint128,
+) -> Result<(), ContractError> {
+    let index = (score.clamp(1, 10) - 1) as usize;
+    let previous_count: u64 = aggregates.histogram.iter().sum();
+
+    aggregates.histogram[index] += 1;
+
+    // Decay the recency weights forward to the newest rating, then add this one
+    if previous_count == 0 {
+        aggregates.weights_updated_at = timestamp;
+    }
+    if timestamp > aggregates.weights_updated_at {
+        let decay = half_life_decay(
+            timestamp.seconds() - aggregates.weights_updated_at.seconds(),
+            RECENCY_HALF_LIFE,
+        );
+        for weight in aggregates.recency_weights.iter_mut() {
+            *weight = weight.checked_mul(decay)?;
+        }
+        aggregates.weights_updated_at = timestamp;
+    }
+    let age = aggregates.weights_updated_at.seconds() - timestamp.seconds();
+    aggregates.recency_weights[index] += half_life_decay(age, RECENCY_HALF_LIFE);
+
+    // Count the rating in its day bucket and drop buckets outside the window
+    let day = timestamp.seconds() / SECONDS_PER_DAY;
+    match aggregates.recent_days.iter_mut().find(|(d, _)| *d == day) {
+        Some((_, count)) => *count += 1,
+        None => aggregates.recent_days.push((day, 1)),
+    }
+    let window_start = aggregates.weights_updated_at.seconds().saturating_sub(RECENT_ACTIVITY_WINDOW);
+    aggregates.recent_days.retain(|(d, _)| (d + 1) * SECONDS_PER_DAY > window_start);
+    aggregates.recent_days.sort_unstable();
+
+    if new_rater {
+        aggregates.unique_raters += 1;
+    }
+
+    // Rater standing is captured when the rating is given
+    if rater_karma.u128() >= HIGH_KARMA_RATER_THRESHOLD {
+        aggregates.high_karma_raters += 1;
+    } else if rater_karma.u128() < LOW_KARMA_RATER_THRESHOLD {
+        aggregates.low_karma_raters += 1;
+    }
+
+    let score = Decimal::from_ratio(score, 1u8);
+    aggregates.moving_average = if previous_count == 0 {
+        score
+    } else {
+        let alpha = Decimal::percent(MOVING_AVERAGE_ALPHA);
+        aggregates.moving_average.checked_mul(Decimal::one() - alpha)? + score.checked_mul(alpha)?
+    };
+
+    aggregates.last_rating_at = std::cmp::max(aggregates.last_rating_at, timestamp);
+
+    Ok(())
+}
+
+/// Take a rating back out of the aggregates, putting `revised` in its place
+/// when the rating was amended
+///
+/// Counts and recency weights are adjusted exactly. The moving average cannot
+/// be unwound, so the rating's step is replaced as if it had been the latest:
+/// with the revised score, or with the average itself when it is retracted.
+fn unfold_rating(
+    aggregates: &mut RatingAggregates,
+    rating: &Rating,
+    revised: Option<u8>,
+    rater_gone: bool,
+    rater_karma: Uint128,
+) -> Result<(), ContractError> {
+    let index = (rating.score.clamp(1, 10) - 1) as usize;
+
+    // The rating's recency weight, decayed forward like the others
+    let age = aggregates.weights_updated_at.seconds().saturating_sub(rating.timestamp.seconds());
+    let weight = std::cmp::min(half_life_decay(age, RECENCY_HALF_LIFE), aggregates.recency_weights[index]);
+    aggregates.histogram[index] = aggregates.histogram[index].saturating_sub(1);
+    aggregates.recency_weights[index] -= weight;
+
+    let replacement = match revised {
+        Some(score) => {
+            let revised_index = (score.clamp(1, 10) - 1) as usize;
+            aggregates.histogram[revised_index] += 1;
+            aggregates.recency_weights[revised_index] += weight;
+            Decimal::from_ratio(score, 1u8)
+        }
+        None => {
+            let day = rating.timestamp.seconds() / SECONDS_PER_DAY;
+            if let Some((_, count)) = aggregates.recent_days.iter_mut().find(|(d, _)| *d == day) {
+                *count = count.saturating_sub(1);
+            }
+            aggregates.recent_days.retain(|(_, count)| *count > 0);
+
+            if rater_gone {
+                aggregates.unique_raters = aggregates.unique_raters.saturating_sub(1);
+            }
+            if rater_karma.u128() >= HIGH_KARMA_RATER_THRESHOLD {
+                aggregates.high_karma_raters = aggregates.high_karma_raters.saturating_sub(1);
+            } else if rater_karma.u128() < LOW_KARMA_RATER_THRESHOLD {
+                aggregates.low_karma_raters = aggregates.low_karma_raters.saturating_sub(1);
+            }
+            aggregates.moving_average
+        }
+    };
+
+    let alpha = Decimal::percent(MOVING_AVERAGE_ALPHA);
+    aggregates.moving_average = if aggregates.histogram.iter().all(|count| *count == 0) {
+        Decimal::zero()
+    } else {
+        (aggregates.moving_average + replacement.checked_mul(alpha)?)
+            .saturating_sub(Decimal::from_ratio(rating.score, 1u8).checked_mul(alpha)?)
+    };
+
+    Ok(())
+}
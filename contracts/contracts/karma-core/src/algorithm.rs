@@ -0,0 +1,329 @@
+use cosmwasm_std::{Decimal, Int128, Uint128};
+use crate::error::ContractError;
+use agent_karma_contracts::types::{
+    KarmaAlgorithmConfig, KarmaAlgorithmParams, KarmaAlgorithmVersion, KarmaModifier,
+};
+
+/// Rating score at or above which a rating counts as high
+pub const HIGH_RATING_THRESHOLD: u8 = 8;
+
+/// Rating score below which a rating counts as poor
+pub const POOR_RATING_THRESHOLD: u8 = 4;
+
+/// Rating score at or below which a rating counts as very poor
+pub const VERY_POOR_RATING_THRESHOLD: u8 = 2;
+
+/// Rater karma at or above which a rater counts as high-karma
+pub const HIGH_KARMA_RATER_THRESHOLD: u128 = 500;
+
+/// Rater karma below which a rater counts as low-karma
+pub const LOW_KARMA_RATER_THRESHOLD: u128 = 100;
+
+/// Rating statistics an algorithm scores an agent from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RatingStats {
+    /// Total number of ratings received
+    pub rating_count: u64,
+    /// Recency and quality weighted average rating
+    pub weighted_average: Decimal,
+    /// Ratings received in the last 30 days
+    pub recent_count: u64,
+    /// Number of distinct raters
+    pub unique_raters: u64,
+    /// Ratings at or above `HIGH_RATING_THRESHOLD`
+    pub high_ratings: u64,
+    /// Ratings below `POOR_RATING_THRESHOLD`
+    pub poor_ratings: u64,
+    /// Ratings at or below `VERY_POOR_RATING_THRESHOLD`
+    pub very_poor_ratings: u64,
+    /// Lifetime average and recent moving average, when there are enough ratings
+    pub trend: Option<(Decimal, Decimal)>,
+    /// Ratings submitted by raters with high karma at the time
+    pub high_karma_raters: u64,
+    /// Ratings submitted by raters with low karma at the time
+    pub low_karma_raters: u64,
+}
+
+/// Oracle scores (0-100) available for an agent
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OracleSignals {
+    pub performance: Option<u32>,
+    pub cross_chain: Option<u32>,
+    pub sentiment: Option<u32>,
+}
+
+/// A karma scoring algorithm
+///
+/// The calculation pipeline in `karma.rs` gathers the rating statistics and
+/// oracle signals; the algorithm turns them into score components. Default
+/// methods implement the rules shared by every version, so a new version only
+/// overrides what it changes. Bonuses are expressed as named modifiers so the
+/// same rules drive both the score and its breakdown.
+pub trait KarmaAlgorithm {
+    /// Version recorded on every calculation produced by this algorithm
+    fn version(&self) -> KarmaAlgorithmVersion;
+
+    /// Parameters the algorithm was configured with
+    fn params(&self) -> &KarmaAlgorithmParams;
+
+    /// Weight of a single rating in the weighted average, before recency weighting
+    fn rating_weight(&self, score: u8) -> Decimal;
+
+    /// Base karma derived from the weighted average rating
+    fn base_score(&self, stats: &RatingStats) -> Result<Uint128, ContractError>;
+
+    /// Interaction bonus rules: participation with diminishing returns on volume
+    fn interaction_bonus_modifiers(&self, stats: &RatingStats) -> Vec<KarmaModifier> {
+        let weight = self.params().interaction_bonus_weight.u128();
+        let count = stats.rating_count as u128;
+
+        // Full weight for the first 10, half up to 50, a quarter beyond
+        let volume_bonus = if count <= 10 {
+            count * weight
+        } else if count <= 50 {
+            10 * weight + (count - 10) * (weight / 2)
+        } else {
+            10 * weight + 40 * (weight / 2) + (count - 50) * (weight / 4)
+        };
+
+        let recent_activity = stats.recent_count >= 5;
+        let diverse_raters = stats.unique_raters >= 5;
+
+        vec![
+            modifier(
+                "interaction_volume",
+                "full weight per rating up to 10, half up to 50, a quarter beyond".to_string(),
+                count > 0,
+                volume_bonus as i128,
+            ),
+            modifier(
+                "recent_activity",
+                "at least 5 ratings in the last 30 days".to_string(),
+                recent_activity,
+                if recent_activity { (weight * 2) as i128 } else { 0 },
+            ),
+            modifier(
+                "rater_diversity",
+                "at least 5 distinct raters".to_string(),
+                diverse_raters,
+                if diverse_raters { weight as i128 } else { 0 },
+            ),
+        ]
+    }
+
+    /// Contextual modifier rules: behaviour-based bonuses and penalties
+    fn contextual_modifiers(&self, stats: &RatingStats) -> Vec<KarmaModifier> {
+        let params = self.params();
+        let consistency_value = params.consistency_bonus_value.u128() as i128;
+        let penalty = params.low_karma_interaction_penalty.u128() as i128;
+
+        // Consistency bonus for agents with many high ratings
+        let consistent = stats.high_ratings >= params.consistency_bonus_threshold;
+
+        // Excellence bonus for exceptional performance (90%+ high ratings)
+        let excellent = stats.rating_count >= 20 && stats.high_ratings * 10 >= stats.rating_count * 9;
+
+        // Improvement bonus for agents showing a positive trend
+        let improving = matches!(
+            stats.trend,
+            Some((lifetime_avg, recent_avg)) if recent_avg > lifetime_avg + Decimal::one()
+        );
+
+        // Small penalty for too many interactions with low-karma agents (potential gaming)
+        let low_karma_heavy = stats.rating_count > 0 && stats.low_karma_raters > stats.rating_count / 2;
+
+        vec![
+            modifier(
+                "consistency_bonus",
+                format!(
+                    "at least {} ratings of {} or higher",
+                    params.consistency_bonus_threshold, HIGH_RATING_THRESHOLD
+                ),
+                consistent,
+                if consistent { consistency_value } else { 0 },
+            ),
+            modifier(
+                "excellence_bonus",
+                format!("at least 20 ratings, 90% of them {} or higher", HIGH_RATING_THRESHOLD),
+                excellent,
+                if excellent { consistency_value * 2 } else { 0 },
+            ),
+            modifier(
+                "poor_rating_penalty",
+                format!("per rating below {}", POOR_RATING_THRESHOLD),
+                stats.poor_ratings > 0,
+                -(stats.poor_ratings as i128 * penalty),
+            ),
+            modifier(
+                "very_poor_rating_penalty",
+                format!("double penalty per rating of {} or lower", VERY_POOR_RATING_THRESHOLD),
+                stats.very_poor_ratings > 0,
+                -(stats.very_poor_ratings as i128 * penalty * 2),
+            ),
+            modifier(
+                "improvement_bonus",
+                "recent moving average more than one point above the lifetime average".to_string(),
+                improving,
+                if improving { (params.interaction_bonus_weight.u128() * 3) as i128 } else { 0 },
+            ),
+            modifier(
+                "high_karma_rater_bonus",
+                format!("per rating from a rater with at least {} karma", HIGH_KARMA_RATER_THRESHOLD),
+                stats.high_karma_raters > 0,
+                (stats.high_karma_raters as u128 * params.high_karma_interaction_bonus.u128()) as i128,
+            ),
+            modifier(
+                "low_karma_rater_penalty",
+                format!("over half of the ratings from raters below {} karma", LOW_KARMA_RATER_THRESHOLD),
+                low_karma_heavy,
+                if low_karma_heavy { -(penalty * 2) } else { 0 },
+            ),
+        ]
+    }
+
+    /// Weighted oracle bonus per signal, before the freshness factor is
+    /// applied; `None` when the signal is missing
+    fn external_factor_terms(&self, signals: &OracleSignals) -> Vec<(&'static str, Option<Decimal>)> {
+        let params = self.params();
+
+        vec![
+            (
+                "performance",
+                signals.performance.map(|score| {
+                    Decimal::from_ratio(score * params.performance_weight as u32 * 2, 1u32)
+                }),
+            ),
+            // Cross-chain reputation is scaled by 1.5
+            (
+                "cross_chain",
+                signals.cross_chain.map(|score| {
+                    Decimal::from_ratio(score * params.cross_chain_weight as u32 * 3, 2u32)
+                }),
+            ),
+            (
+                "sentiment",
+                signals.sentiment.map(|score| {
+                    Decimal::from_ratio(score * params.sentiment_weight as u32, 1u32)
+                }),
+            ),
+        ]
+    }
+}
+
+/// Build a modifier entry for a score breakdown
+pub fn modifier(name: &str, rule: String, fired: bool, contribution: i128) -> KarmaModifier {
+    KarmaModifier {
+        name: name.to_string(),
+        rule,
+        fired,
+        contribution: Int128::new(contribution),
+    }
+}
+
+/// Total contribution of a list of modifiers
+pub fn sum_modifiers(modifiers: &[KarmaModifier]) -> i128 {
+    modifiers.iter().map(|m| m.contribution.i128()).sum()
+}
+
+/// Original algorithm: quadratic growth above a neutral average, extreme
+/// ratings damped to 80% weight to make gaming harder
+pub struct KarmaAlgorithmV1 {
+    params: KarmaAlgorithmParams,
+}
+
+impl KarmaAlgorithm for KarmaAlgorithmV1 {
+    fn version(&self) -> KarmaAlgorithmVersion {
+        KarmaAlgorithmVersion::V1
+    }
+
+    fn params(&self) -> &KarmaAlgorithmParams {
+        &self.params
+    }
+
+    fn rating_weight(&self, score: u8) -> Decimal {
+        if score <= 2 || score >= 9 {
+            Decimal::percent(80)
+        } else {
+            Decimal::one()
+        }
+    }
+
+    fn base_score(&self, stats: &RatingStats) -> Result<Uint128, ContractError> {
+        let neutral = Decimal::from_ratio(5u8, 1u8);
+        let weight = self.params.base_rating_weight;
+
+        if stats.weighted_average >= neutral {
+            // Square of the distance above neutral (0.0 to 1.0)
+            let positive_factor = (stats.weighted_average - neutral) / neutral;
+            let exponential_factor = positive_factor.checked_mul(positive_factor)?;
+            Ok(weight.checked_mul(Uint128::from(stats.rating_count))? * exponential_factor)
+        } else {
+            // Minimal karma for poor ratings (handled in contextual modifiers)
+            let poor_factor = (neutral - stats.weighted_average) / Decimal::from_ratio(4u8, 1u8);
+            let remaining_factor = Decimal::one().saturating_sub(poor_factor);
+            Ok(weight * remaining_factor.checked_mul(Decimal::percent(10))?)
+        }
+    }
+}
+
+/// Linear algorithm: karma grows proportionally with the distance above a
+/// neutral average and every rating carries the same weight
+pub struct KarmaAlgorithmV2 {
+    params: KarmaAlgorithmParams,
+}
+
+impl KarmaAlgorithm for KarmaAlgorithmV2 {
+    fn version(&self) -> KarmaAlgorithmVersion {
+        KarmaAlgorithmVersion::V2
+    }
+
+    fn params(&self) -> &KarmaAlgorithmParams {
+        &self.params
+    }
+
+    fn rating_weight(&self, _score: u8) -> Decimal {
+        Decimal::one()
+    }
+
+    fn base_score(&self, stats: &RatingStats) -> Result<Uint128, ContractError> {
+        let neutral = Decimal::from_ratio(5u8, 1u8);
+
+        if stats.weighted_average <= neutral {
+            return Ok(Uint128::zero());
+        }
+
+        let positive_factor = (stats.weighted_average - neutral) / neutral;
+        Ok(self.params.base_rating_weight.checked_mul(Uint128::from(stats.rating_count))? * positive_factor)
+    }
+}
+
+/// Instantiate the algorithm selected in the karma configuration
+pub fn load_algorithm(config: &KarmaAlgorithmConfig) -> Box<dyn KarmaAlgorithm> {
+    let params = config.params.clone();
+    match config.version {
+        KarmaAlgorithmVersion::V1 => Box::new(KarmaAlgorithmV1 { params }),
+        KarmaAlgorithmVersion::V2 => Box::new(KarmaAlgorithmV2 { params }),
+    }
+}
+
+/// Validate algorithm parameters before they are stored
+pub fn validate_algorithm_config(config: &KarmaAlgorithmConfig) -> Result<(), ContractError> {
+    let params = &config.params;
+
+    if params.max_karma_score.is_zero() {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Maximum karma score cannot be zero".to_string(),
+        });
+    }
+
+    let oracle_weight = params.performance_weight as u16
+        + params.cross_chain_weight as u16
+        + params.sentiment_weight as u16;
+    if oracle_weight > 100 {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Oracle weights cannot exceed 100 percent".to_string(),
+        });
+    }
+
+    Ok(())
+}
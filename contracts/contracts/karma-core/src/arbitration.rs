@@ -0,0 +1,400 @@
+use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult, Storage};
+use sha2::{Digest, Sha256};
+use crate::compliance::{parse_dispute_resolution, DisputeResolution, DisputeStatus};
+use crate::error::ContractError;
+use crate::helpers::award_karma;
+use crate::ledger::{record_karma_movement, Posting};
+use crate::state::{
+    Arbitration, JurorVote, ARBITRATIONS, DISPUTE_CASES, JUROR_VOTES, KARMA_SCORES, LEADERBOARD,
+};
+use agent_karma_contracts::types::{ArbitrationConfig, KarmaLedgerReason};
+
+/// Highest-ranked agents considered when drawing a juror panel
+const MAX_JUROR_CANDIDATES: usize = 100;
+
+/// Resolutions jurors can vote for
+const RESOLUTIONS: [DisputeResolution; 3] = [
+    DisputeResolution::ViolationConfirmed,
+    DisputeResolution::ViolationOverturned,
+    DisputeResolution::PartialOverturned,
+];
+
+/// Blocks between filing a dispute and drawing its juror panel
+pub const JUROR_DRAW_DELAY: u64 = 5;
+
+/// Outcome of a call for a juror draw
+#[derive(Clone, Debug, PartialEq)]
+pub enum JurorDraw {
+    /// Panel drawn; empty when no eligible jurors were left
+    Drawn(Vec<Addr>),
+    /// The scheduled block passed without a draw; height of the new one
+    Rescheduled(u64),
+}
+
+/// Outcome of a finalized arbitration
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArbitrationVerdict {
+    pub resolution: DisputeResolution,
+    /// Jurors who voted with the majority and earned karma
+    pub rewarded: Vec<Addr>,
+    /// Jurors who never revealed a vote and lost karma
+    pub penalized: Vec<Addr>,
+}
+
+/// Validate arbitration parameters before they are stored
+pub fn validate_arbitration_config(config: &ArbitrationConfig) -> Result<(), ContractError> {
+    if config.panel_size.is_multiple_of(2) {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Juror panel size must be odd".to_string(),
+        });
+    }
+
+    if config.commit_period == 0 || config.reveal_period == 0 {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Arbitration commit and reveal periods cannot be zero".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Agents eligible to sit on a dispute's panel
+///
+/// Candidates are the top `MAX_JUROR_CANDIDATES` agents of the leaderboard
+/// holding at least `min_juror_karma`, less the parties to the dispute.
+fn juror_candidates(
+    storage: &dyn Storage,
+    config: &ArbitrationConfig,
+    excluded: &[&Addr],
+) -> StdResult<Vec<String>> {
+    let mut candidates = vec![];
+    for item in LEADERBOARD.keys(storage, None, None, Order::Ascending) {
+        let (key, agent) = item?;
+        if u128::MAX - key < config.min_juror_karma.u128() {
+            break;
+        }
+        if excluded.iter().any(|party| party.as_str() == agent) {
+            continue;
+        }
+        candidates.push(agent);
+        if candidates.len() == MAX_JUROR_CANDIDATES {
+            break;
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Draw the juror panel of a dispute from the highest-karma agents
+///
+/// The panel is made of the candidates whose hash with the seed sorts first,
+/// so anyone can recompute it.
+pub fn draw_jurors(
+    storage: &dyn Storage,
+    config: &ArbitrationConfig,
+    seed: &[u8],
+    excluded: &[&Addr],
+) -> StdResult<Vec<Addr>> {
+    let mut candidates: Vec<_> = juror_candidates(storage, config, excluded)?
+        .into_iter()
+        .map(|agent| (juror_draw_hash(seed, &agent), agent))
+        .collect();
+
+    candidates.sort();
+    Ok(candidates
+        .into_iter()
+        .take(config.panel_size as usize)
+        .map(|(_, agent)| Addr::unchecked(agent))
+        .collect())
+}
+
+/// Set a new dispute up for a juror draw `JUROR_DRAW_DELAY` blocks later, and
+/// return the height of the drawing block
+///
+/// With no eligible jurors nothing is scheduled and the dispute is left to the
+/// admin.
+pub fn schedule_juror_draw(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &ArbitrationConfig,
+    case_id: &str,
+    excluded: &[&Addr],
+) -> StdResult<Option<u64>> {
+    if juror_candidates(storage, config, excluded)?.is_empty() {
+        return Ok(None);
+    }
+
+    let draw_height = env.block.height + JUROR_DRAW_DELAY;
+    let mut dispute_case = DISPUTE_CASES.load(storage, case_id)?;
+    dispute_case.juror_draw_height = Some(draw_height);
+    DISPUTE_CASES.save(storage, case_id, &dispute_case)?;
+
+    Ok(Some(draw_height))
+}
+
+/// Put a dispute under arbitration by a panel drawn in the block scheduled
+/// when it was filed
+///
+/// The draw is seeded with the time of that block, which is unknown when the
+/// dispute is filed and cannot be chosen by whoever calls for the draw. A
+/// draw nobody called for in its block is scheduled again `JUROR_DRAW_DELAY`
+/// blocks later. If no eligible jurors are left nothing is stored and the
+/// dispute is left to the admin.
+pub fn open_arbitration(
+    storage: &mut dyn Storage,
+    env: &Env,
+    config: &ArbitrationConfig,
+    case_id: &str,
+    excluded: &[&Addr],
+) -> Result<JurorDraw, ContractError> {
+    let mut dispute_case = DISPUTE_CASES.load(storage, case_id)?;
+    let Some(draw_height) = dispute_case.juror_draw_height else {
+        if ARBITRATIONS.has(storage, case_id) {
+            return Err(phase_closed(case_id, "juror draw"));
+        }
+        return Err(ContractError::ArbitrationNotFound {
+            case_id: case_id.to_string(),
+        });
+    };
+    if env.block.height < draw_height {
+        return Err(phase_closed(case_id, "juror draw"));
+    }
+    if env.block.height > draw_height {
+        let draw_height = env.block.height + JUROR_DRAW_DELAY;
+        dispute_case.juror_draw_height = Some(draw_height);
+        DISPUTE_CASES.save(storage, case_id, &dispute_case)?;
+        return Ok(JurorDraw::Rescheduled(draw_height));
+    }
+
+    dispute_case.juror_draw_height = None;
+    let jurors = draw_jurors(storage, config, &juror_draw_seed(case_id, env), excluded)?;
+    if jurors.is_empty() {
+        DISPUTE_CASES.save(storage, case_id, &dispute_case)?;
+        return Ok(JurorDraw::Drawn(jurors));
+    }
+
+    let commit_deadline = env.block.time.plus_seconds(config.commit_period);
+    let arbitration = Arbitration {
+        jurors: jurors.clone(),
+        commit_deadline,
+        reveal_deadline: commit_deadline.plus_seconds(config.reveal_period),
+        resolution: None,
+    };
+    ARBITRATIONS.save(storage, case_id, &arbitration)?;
+
+    dispute_case.status = DisputeStatus::UnderReview;
+    DISPUTE_CASES.save(storage, case_id, &dispute_case)?;
+
+    Ok(JurorDraw::Drawn(jurors))
+}
+
+/// Commitment a juror submits for a vote: hex SHA-256 of
+/// `"{case_id}:{juror}:{resolution}:{salt}"`
+pub fn vote_commitment(case_id: &str, juror: &str, resolution: &str, salt: &str) -> String {
+    let preimage = format!("{}:{}:{}:{}", case_id, juror, resolution, salt);
+    hex::encode(Sha256::digest(preimage.as_bytes()))
+}
+
+/// Record a juror's sealed vote during the commit phase
+pub fn commit_vote(
+    storage: &mut dyn Storage,
+    env: &Env,
+    case_id: &str,
+    juror: &Addr,
+    commitment: String,
+) -> Result<(), ContractError> {
+    let arbitration = load_panel(storage, case_id, juror)?;
+
+    if arbitration.resolution.is_some() || env.block.time > arbitration.commit_deadline {
+        return Err(phase_closed(case_id, "commit"));
+    }
+    if JUROR_VOTES.has(storage, (case_id, juror.as_str())) {
+        return Err(ContractError::JurorVoteAlreadyRecorded {
+            case_id: case_id.to_string(),
+            action: "committed".to_string(),
+        });
+    }
+
+    let vote = JurorVote {
+        commitment: commitment.to_lowercase(),
+        revealed: None,
+    };
+    JUROR_VOTES.save(storage, (case_id, juror.as_str()), &vote)?;
+
+    Ok(())
+}
+
+/// Open a juror's sealed vote during the reveal phase
+///
+/// The reveal phase starts once the commit phase is over, or as soon as every
+/// juror has committed.
+pub fn reveal_vote(
+    storage: &mut dyn Storage,
+    env: &Env,
+    case_id: &str,
+    juror: &Addr,
+    resolution: &str,
+    salt: &str,
+) -> Result<DisputeResolution, ContractError> {
+    let arbitration = load_panel(storage, case_id, juror)?;
+
+    let reveal_open = env.block.time > arbitration.commit_deadline
+        || all_jurors(storage, case_id, &arbitration, |_| true)?;
+    if arbitration.resolution.is_some() || !reveal_open || env.block.time > arbitration.reveal_deadline {
+        return Err(phase_closed(case_id, "reveal"));
+    }
+
+    let invalid = || ContractError::InvalidVoteReveal {
+        case_id: case_id.to_string(),
+    };
+    let mut vote = JUROR_VOTES
+        .may_load(storage, (case_id, juror.as_str()))?
+        .ok_or_else(invalid)?;
+    if vote.revealed.is_some() {
+        return Err(ContractError::JurorVoteAlreadyRecorded {
+            case_id: case_id.to_string(),
+            action: "revealed".to_string(),
+        });
+    }
+
+    let parsed = parse_dispute_resolution(resolution)?;
+    if vote_commitment(case_id, juror.as_str(), resolution, salt) != vote.commitment {
+        return Err(invalid());
+    }
+
+    vote.revealed = Some(parsed.clone());
+    JUROR_VOTES.save(storage, (case_id, juror.as_str()), &vote)?;
+
+    Ok(parsed)
+}
+
+/// Settle the jurors' votes once the reveal phase is over, or every juror
+/// has revealed
+///
+/// A resolution needs more than half of the revealed votes; without one the
+/// violation stands. Jurors in the majority earn `juror_reward`, jurors who
+/// never revealed lose `non_reveal_penalty`. The dispute itself still needs
+/// resolving with the verdict.
+pub fn finalize_arbitration(
+    mut deps: DepsMut,
+    env: &Env,
+    config: &ArbitrationConfig,
+    case_id: &str,
+) -> Result<ArbitrationVerdict, ContractError> {
+    let mut arbitration = ARBITRATIONS
+        .may_load(deps.storage, case_id)?
+        .ok_or_else(|| ContractError::ArbitrationNotFound {
+            case_id: case_id.to_string(),
+        })?;
+    if arbitration.resolution.is_some() {
+        return Err(ContractError::DisputeAlreadyResolved {
+            case_id: case_id.to_string(),
+        });
+    }
+
+    let voting_over = env.block.time > arbitration.reveal_deadline
+        || all_jurors(deps.storage, case_id, &arbitration, |vote| vote.revealed.is_some())?;
+    if !voting_over {
+        return Err(phase_closed(case_id, "finalization"));
+    }
+
+    let mut votes = vec![];
+    for juror in arbitration.jurors.iter() {
+        let vote = JUROR_VOTES.may_load(deps.storage, (case_id, juror.as_str()))?;
+        votes.push((juror.clone(), vote.and_then(|vote| vote.revealed)));
+    }
+
+    let revealed: Vec<&DisputeResolution> = votes.iter().filter_map(|(_, vote)| vote.as_ref()).collect();
+    let majority = RESOLUTIONS
+        .into_iter()
+        .find(|resolution| revealed.iter().filter(|vote| **vote == resolution).count() * 2 > revealed.len());
+
+    let mut verdict = ArbitrationVerdict {
+        resolution: majority.clone().unwrap_or(DisputeResolution::ViolationConfirmed),
+        rewarded: vec![],
+        penalized: vec![],
+    };
+    for (juror, vote) in votes {
+        match vote {
+            Some(vote) if majority.as_ref() == Some(&vote) => {
+                let posting = Posting::new(KarmaLedgerReason::JurorReward, None, Some(case_id));
+                award_karma(deps.branch(), env, &juror, config.juror_reward, posting)?;
+                verdict.rewarded.push(juror);
+            }
+            Some(_) => {}
+            None => {
+                let mut karma = KARMA_SCORES.may_load(deps.storage, juror.as_str())?.unwrap_or_default();
+                let before = karma.current_score;
+                karma.current_score = karma.current_score.saturating_sub(config.non_reveal_penalty);
+                KARMA_SCORES.save(deps.storage, juror.as_str(), &karma)?;
+                let posting = Posting::new(KarmaLedgerReason::JurorPenalty, None, Some(case_id));
+                record_karma_movement(deps.storage, env, juror.as_str(), before, karma.current_score, posting)?;
+                verdict.penalized.push(juror);
+            }
+        }
+    }
+
+    arbitration.resolution = Some(verdict.resolution.clone());
+    ARBITRATIONS.save(deps.storage, case_id, &arbitration)?;
+
+    Ok(verdict)
+}
+
+/// Load a dispute's panel, checking that `juror` sits on it
+fn load_panel(storage: &dyn Storage, case_id: &str, juror: &Addr) -> Result<Arbitration, ContractError> {
+    let arbitration = ARBITRATIONS
+        .may_load(storage, case_id)?
+        .ok_or_else(|| ContractError::ArbitrationNotFound {
+            case_id: case_id.to_string(),
+        })?;
+
+    if !arbitration.jurors.contains(juror) {
+        return Err(ContractError::NotAJuror {
+            address: juror.to_string(),
+            case_id: case_id.to_string(),
+        });
+    }
+
+    Ok(arbitration)
+}
+
+/// Whether every juror has committed a vote satisfying `predicate`
+fn all_jurors(
+    storage: &dyn Storage,
+    case_id: &str,
+    arbitration: &Arbitration,
+    predicate: impl Fn(&JurorVote) -> bool,
+) -> StdResult<bool> {
+    for juror in arbitration.jurors.iter() {
+        match JUROR_VOTES.may_load(storage, (case_id, juror.as_str()))? {
+            Some(vote) if predicate(&vote) => {}
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// Seed of a dispute's juror draw: SHA-256 of the case ID, then the height
+/// and nanosecond time of the scheduled drawing block
+fn juror_draw_seed(case_id: &str, env: &Env) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(case_id.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(env.block.height.to_be_bytes());
+    hasher.update(env.block.time.nanos().to_be_bytes());
+    hasher.finalize().into()
+}
+
+fn juror_draw_hash(seed: &[u8], agent: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(seed);
+    hasher.update(agent.as_bytes());
+    hasher.finalize().into()
+}
+
+fn phase_closed(case_id: &str, phase: &str) -> ContractError {
+    ContractError::ArbitrationPhaseClosed {
+        case_id: case_id.to_string(),
+        phase: phase.to_string(),
+    }
+}
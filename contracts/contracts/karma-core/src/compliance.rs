@@ -3,18 +3,23 @@
 //! This module implements automated detection of spam ratings, malicious behavior patterns,
 //! and provides mechanisms for karma penalties and dispute resolution.
 
-use cosmwasm_std::{Addr, Deps, DepsMut, Env, Timestamp, Uint128, StdResult, Order};
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, Timestamp, Uint128, StdResult, Storage, Order};
 use cw_storage_plus::Bound;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use agent_karma_contracts::types::Rating;
+use agent_karma_contracts::types::{EnforcementAction, KarmaLedgerReason};
+use crate::algorithm::HIGH_RATING_THRESHOLD;
 use crate::error::ContractError;
+use crate::helpers::query_registered_agent;
+use crate::ledger::{record_karma_movement, Posting};
 use crate::state::{
-    ratings, KARMA_SCORES, DISPUTE_CASES,
-    RATE_LIMIT_TRACKERS, KARMA_PENALTIES,
+    ratings, StoredRating, CONFIG, RATING_INDEX_BACKFILL, KARMA_SCORES, DISPUTE_CASES, DISPUTE_COUNTER, COMPLIANCE_VIOLATIONS,
+    AGENT_VIOLATIONS, DISPUTE_INDEX_MIGRATION, FORFEITED_STAKE_POOL, KARMA_PENALTIES, VIOLATION_COUNTER,
+    VIOLATION_DISPUTES,
 };
+use crate::rate_limit::{actions_since, consume_quota, rate_limit_config};
 
 /// Abuse detection patterns and thresholds
 pub const SPAM_RATING_THRESHOLD: u32 = 10; // Max ratings per hour
@@ -23,6 +28,13 @@ pub const MIN_RATING_VARIANCE: f64 = 0.5; // Minimum variance in rating scores
 pub const SUSPICIOUS_INTERACTION_RATIO: f64 = 0.8; // Max ratio of interactions with same agents
 pub const BOT_BEHAVIOR_THRESHOLD: u32 = 50; // Max actions per hour for bot detection
 pub const KARMA_PENALTY_MULTIPLIER: u128 = 10; // Penalty multiplier for violations
+pub const MIN_RING_SIZE: usize = 3; // Smallest closed group reported as a clique
+pub const MAX_RING_SIZE: usize = 6; // Largest closed group inspected for cliques
+pub const NEW_AGENT_WINDOW: u64 = 7 * 24 * 3600; // Agents registered within 7 days count as new
+pub const RATING_BURST_THRESHOLD: u32 = 5; // Ratings between new agents in 1 hour
+pub const COLLUSION_WINDOW: u64 = 30 * 24 * 3600; // Ratings exchanged within 30 days form the rating graph
+pub const PARTIAL_OVERTURN_PERCENT: u128 = 50; // Share of stake and penalty returned on a partial overturn
+pub const DISPUTE_REWARD_PERCENT: u128 = 50; // Reward for a full overturn, as a share of the stake
 
 /// Types of compliance violations
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -32,6 +44,34 @@ pub enum ViolationType {
     BotBehavior,
     SuspiciousPattern,
     RateLimitExceeded,
+    CollusionRing,
+}
+
+impl ViolationType {
+    /// Name of the violation type in messages and enforcement policies
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ViolationType::SpamRating => "spam_rating",
+            ViolationType::RatingManipulation => "rating_manipulation",
+            ViolationType::BotBehavior => "bot_behavior",
+            ViolationType::SuspiciousPattern => "suspicious_pattern",
+            ViolationType::RateLimitExceeded => "rate_limit_exceeded",
+            ViolationType::CollusionRing => "collusion_ring",
+        }
+    }
+}
+
+/// Parse a violation type name, as accepted by `ApplyCompliancePenalty`
+pub fn parse_violation_type(name: &str) -> Option<ViolationType> {
+    match name {
+        "spam_rating" => Some(ViolationType::SpamRating),
+        "rating_manipulation" => Some(ViolationType::RatingManipulation),
+        "bot_behavior" => Some(ViolationType::BotBehavior),
+        "suspicious_pattern" => Some(ViolationType::SuspiciousPattern),
+        "rate_limit_exceeded" => Some(ViolationType::RateLimitExceeded),
+        "collusion_ring" => Some(ViolationType::CollusionRing),
+        _ => None,
+    }
 }
 
 /// Compliance violation record
@@ -44,6 +84,21 @@ pub struct ComplianceViolation {
     pub evidence: String,
     pub penalty_applied: Uint128,
     pub disputed: bool,
+    /// Part of the penalty given back after a dispute
+    #[serde(default)]
+    pub penalty_refunded: Uint128,
+    /// Whether a dispute overturned the violation outright
+    #[serde(default)]
+    pub overturned: bool,
+    /// Policy action that recorded the violation; `None` for manual penalties
+    #[serde(default)]
+    pub enforcement: Option<EnforcementAction>,
+    /// Part of the penalty recovered in good standing
+    #[serde(default)]
+    pub penalty_recovered: Uint128,
+    /// Dispute case contesting the violation until it is resolved
+    #[serde(default)]
+    pub open_dispute: Option<String>,
 }
 
 /// Dispute case for false positive detections
@@ -58,6 +113,9 @@ pub struct DisputeCase {
     pub created_at: Timestamp,
     pub resolved_at: Option<Timestamp>,
     pub resolution: Option<DisputeResolution>,
+    /// Block the juror panel is to be drawn in, while the draw is pending
+    #[serde(default)]
+    pub juror_draw_height: Option<u64>,
 }
 
 /// Status of a dispute case
@@ -77,14 +135,17 @@ pub enum DisputeResolution {
     PartialOverturned,
 }
 
-/// Rate limiting tracker
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct RateLimitTracker {
-    pub agent_address: Addr,
-    pub action_type: String,
-    pub count: u32,
-    pub window_start: Timestamp,
-    pub last_action: Timestamp,
+/// Karma moved by a dispute resolution
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisputeOutcome {
+    /// Agent whose penalty was (partly) refunded
+    pub refunded_agent: Option<Addr>,
+    pub penalty_refunded: Uint128,
+    pub stake_returned: Uint128,
+    /// Paid to the challenger out of the forfeited-stake pool
+    pub reward: Uint128,
+    /// Challenger paid back stake or a reward
+    pub challenger: Option<Addr>,
 }
 
 /// Abuse pattern detection result
@@ -106,21 +167,8 @@ pub fn detect_spam_ratings(
     let current_time = env.block.time;
     let window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW);
     
-    // Get all ratings and filter by time and rater
-    let recent_ratings: StdResult<Vec<_>> = ratings()
-        .range(deps.storage, None, None, Order::Ascending)
-        .filter(|item| {
-            match item {
-                Ok((_, stored_rating)) => {
-                    stored_rating.rating.rater_address == *agent_address &&
-                    stored_rating.rating.timestamp >= window_start
-                },
-                Err(_) => true,
-            }
-        })
-        .collect();
-    
-    let ratings_list = recent_ratings?;
+    // Get the agent's ratings from the last hour
+    let ratings_list = ratings_given_since(deps, agent_address.as_str(), window_start)?;
     let rating_count = ratings_list.len() as u32;
     
     let mut evidence = Vec::new();
@@ -138,7 +186,7 @@ pub fn detect_spam_ratings(
     if rating_count > 5 {
         let scores: Vec<u8> = ratings_list
             .iter()
-            .map(|(_, stored_rating)| stored_rating.rating.score)
+            .map(|stored_rating| stored_rating.rating.score)
             .collect();
         
         let variance = calculate_variance(&scores);
@@ -151,7 +199,7 @@ pub fn detect_spam_ratings(
     
     // Check for rating the same agents repeatedly
     let mut target_counts: HashMap<String, u32> = HashMap::new();
-    for (_, stored_rating) in &ratings_list {
+    for stored_rating in &ratings_list {
         let target = stored_rating.rating.rated_address.to_string();
         *target_counts.entry(target).or_insert(0) += 1;
     }
@@ -187,28 +235,28 @@ pub fn detect_bot_behavior(
     agent_address: &Addr,
 ) -> StdResult<AbuseDetectionResult> {
     let current_time = env.block.time;
-    let _window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW);
+    let window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW);
     
-    // Check rate limiting tracker for this agent
-    let rate_tracker = RATE_LIMIT_TRACKERS.may_load(deps.storage, agent_address.as_str())?;
+    // Count the agent's actions of every type over the last hour
+    let (count, first_action) = actions_since(deps.storage, agent_address.as_str(), window_start)?;
     
     let mut evidence = Vec::new();
     let mut is_suspicious = false;
     let mut confidence_score = 0.0;
     
-    if let Some(tracker) = rate_tracker {
+    if let Some(first_action) = first_action {
         // Check if actions exceed bot threshold
-        if tracker.count > BOT_BEHAVIOR_THRESHOLD {
+        if count > BOT_BEHAVIOR_THRESHOLD as u128 {
             is_suspicious = true;
             confidence_score += 0.5;
-            evidence.push(format!("High action frequency: {} actions in 1 hour", tracker.count));
+            evidence.push(format!("High action frequency: {} actions in 1 hour", count));
         }
         
         // Check for perfectly regular timing (bot-like)
-        let time_since_start = current_time.seconds() - tracker.window_start.seconds();
+        let time_since_start = current_time.seconds() - first_action.seconds();
         if time_since_start > 0 {
-            let average_interval = time_since_start / (tracker.count as u64);
-            if average_interval < 10 && tracker.count > 20 {
+            let average_interval = time_since_start / (count as u64);
+            if average_interval < 10 && count > 20 {
                 is_suspicious = true;
                 confidence_score += 0.3;
                 evidence.push(format!("Regular timing pattern: {}s average interval", average_interval));
@@ -241,58 +289,34 @@ pub fn detect_rating_manipulation(
     let window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW * 24); // 24 hour window
     
     // Get ratings given by this agent
-    let given_ratings: StdResult<Vec<_>> = ratings()
-        .idx
-        .rater
-        .prefix(agent_address.to_string())
-        .range(deps.storage, None, None, Order::Ascending)
-        .filter(|item| {
-            match item {
-                Ok((_, stored_rating)) => stored_rating.rating.timestamp >= window_start,
-                Err(_) => true,
-            }
-        })
-        .collect();
-    
-    // Get ratings received by this agent
-    let received_ratings: StdResult<Vec<_>> = ratings()
-        .idx
-        .rated_agent
-        .prefix(agent_address.to_string())
-        .range(deps.storage, None, None, Order::Ascending)
-        .filter(|item| {
-            match item {
-                Ok((_, stored_rating)) => stored_rating.rating.timestamp >= window_start,
-                Err(_) => true,
-            }
-        })
-        .collect();
-    
-    let given_list = given_ratings?;
-    let received_list = received_ratings?;
+    let given_list = ratings_given_since(deps, agent_address.as_str(), window_start)?;
     
     let mut evidence = Vec::new();
     let mut is_suspicious = false;
     let mut confidence_score = 0.0;
     
-    // Check for reciprocal rating patterns (quid pro quo)
+    // Check for reciprocal rating patterns (quid pro quo), looking up the
+    // recent ratings of each rated agent once
+    let mut returned_ratings: HashMap<String, Vec<Timestamp>> = HashMap::new();
     let mut reciprocal_count = 0;
-    for (_, given_rating) in &given_list {
-        let target = &given_rating.rating.rated_address;
+    for given_rating in &given_list {
+        let target = given_rating.rating.rated_address.to_string();
+        if !returned_ratings.contains_key(&target) {
+            let returned = ratings_given_since(deps, &target, window_start)?
+                .into_iter()
+                .filter(|rating| rating.rating.rated_address == *agent_address)
+                .map(|rating| rating.rating.timestamp)
+                .collect();
+            returned_ratings.insert(target.clone(), returned);
+        }
         
         // Check if target also rated this agent recently
-        for (_, received_rating) in &received_list {
-            if received_rating.rating.rater_address == *target {
-                let time_diff = if given_rating.rating.timestamp > received_rating.rating.timestamp {
-                    given_rating.rating.timestamp.seconds() - received_rating.rating.timestamp.seconds()
-                } else {
-                    received_rating.rating.timestamp.seconds() - given_rating.rating.timestamp.seconds()
-                };
-                
-                // If ratings are within 1 hour of each other, it's suspicious
-                if time_diff < 3600 {
-                    reciprocal_count += 1;
-                }
+        for received_at in &returned_ratings[&target] {
+            let time_diff = given_rating.rating.timestamp.seconds().abs_diff(received_at.seconds());
+            
+            // If ratings are within 1 hour of each other, it's suspicious
+            if time_diff < 3600 {
+                reciprocal_count += 1;
             }
         }
     }
@@ -309,7 +333,7 @@ pub fn detect_rating_manipulation(
     // Check for coordinated rating attacks (multiple low ratings in short time)
     let low_ratings_given = given_list
         .iter()
-        .filter(|(_, rating)| rating.rating.score <= 3)
+        .filter(|rating| rating.rating.score <= 3)
         .count();
     
     if low_ratings_given > 5 && given_list.len() > 0 {
@@ -336,17 +360,245 @@ pub fn detect_rating_manipulation(
     })
 }
 
+/// Ratings an agent gave at or after `since`, oldest first, read from the
+/// (rater, timestamp) index
+fn ratings_given_since(deps: Deps, rater: &str, since: Timestamp) -> StdResult<Vec<StoredRating>> {
+    ratings()
+        .idx
+        .rater_timestamp
+        .sub_prefix(rater.to_string())
+        .range(
+            deps.storage,
+            Some(Bound::inclusive((since.seconds(), String::new()))),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| item.map(|(_, stored_rating)| stored_rating))
+        .collect()
+}
+
+/// Ratings an agent received at or after `since`, oldest first, read from
+/// the (rated agent, timestamp) index
+fn ratings_received_since(deps: Deps, rated: &str, since: Timestamp) -> StdResult<Vec<StoredRating>> {
+    ratings()
+        .idx
+        .rated_timestamp
+        .sub_prefix(rated.to_string())
+        .range(
+            deps.storage,
+            Some(Bound::inclusive((since.seconds(), String::new()))),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| item.map(|(_, stored_rating)| stored_rating))
+        .collect()
+}
+
+/// Re-save up to `limit` ratings stored before the composite (rater,
+/// timestamp) and (rated agent, timestamp) indexes existed, resuming after the last one re-saved; returns the number
+/// re-saved and whether every rating is now indexed
+pub fn backfill_rating_index(storage: &mut dyn Storage, limit: u32) -> StdResult<(u32, bool)> {
+    let start_after = RATING_INDEX_BACKFILL.may_load(storage)?;
+    let batch = ratings()
+        .range(storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit as usize + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let completed = batch.len() <= limit as usize;
+
+    let mut backfilled = 0;
+    for (rating_id, stored_rating) in batch.into_iter().take(limit as usize) {
+        // Saving over the rating writes the index entries it is missing
+        ratings().save(storage, &rating_id, &stored_rating)?;
+        RATING_INDEX_BACKFILL.save(storage, &rating_id)?;
+        backfilled += 1;
+    }
+
+    Ok((backfilled, completed))
+}
+
+/// Ratings exchanged with each counterpart, keyed by address: (count, score sum)
+type RatingEdges = BTreeMap<String, (u32, u32)>;
+
+/// Ratings an agent gave and received at or after `since`, aggregated per
+/// counterpart
+fn rating_edges(deps: Deps, agent_address: &str, since: Timestamp) -> StdResult<(RatingEdges, RatingEdges)> {
+    let mut given = RatingEdges::new();
+    for stored_rating in ratings_given_since(deps, agent_address, since)? {
+        let edge = given.entry(stored_rating.rating.rated_address.to_string()).or_insert((0, 0));
+        edge.0 += 1;
+        edge.1 += stored_rating.rating.score as u32;
+    }
+
+    let mut received = RatingEdges::new();
+    for stored_rating in ratings_received_since(deps, agent_address, since)? {
+        let edge = received.entry(stored_rating.rating.rater_address.to_string()).or_insert((0, 0));
+        edge.0 += 1;
+        edge.1 += stored_rating.rating.score as u32;
+    }
+
+    Ok((given, received))
+}
+
+/// Counterparts rated high in both directions
+fn mutual_high_partners(given: &RatingEdges, received: &RatingEdges) -> BTreeSet<String> {
+    let is_high = |(count, sum): &(u32, u32)| *sum >= *count * HIGH_RATING_THRESHOLD as u32;
+
+    given
+        .iter()
+        .filter(|(partner, edge)| {
+            is_high(edge) && received.get(partner.as_str()).is_some_and(is_high)
+        })
+        .map(|(partner, _)| partner.clone())
+        .collect()
+}
+
+/// Detect collusion rings from the rating graph
+///
+/// Looks for agents that rate each other highly, small groups whose ratings
+/// never leave the group, and bursts of ratings exchanged between newly
+/// registered agents. The rating graph only covers the last
+/// `COLLUSION_WINDOW`. Ring members are listed in the evidence.
+pub fn detect_collusion_ring(
+    deps: Deps,
+    env: &Env,
+    agent_address: &Addr,
+) -> StdResult<AbuseDetectionResult> {
+    let current_time = env.block.time;
+    let window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW);
+    let graph_start = Timestamp::from_seconds(current_time.seconds().saturating_sub(COLLUSION_WINDOW));
+
+    let (given, received) = rating_edges(deps, agent_address.as_str(), graph_start)?;
+    let partners = mutual_high_partners(&given, &received);
+
+    let mut members: BTreeSet<String> = BTreeSet::new();
+    let mut evidence = Vec::new();
+    let mut is_suspicious = false;
+    let mut confidence_score = 0.0;
+
+    // Check for mutual high-rating pairs
+    if !partners.is_empty() {
+        is_suspicious = true;
+        confidence_score += 0.3;
+        evidence.push(format!("Mutual high ratings with {} agents", partners.len()));
+        members.extend(partners.iter().cloned());
+    }
+
+    // Check whether the agent and its partners form a clique rating only each other
+    let group_size = partners.len() + 1;
+    if (MIN_RING_SIZE..=MAX_RING_SIZE).contains(&group_size) {
+        let mut group = partners.clone();
+        group.insert(agent_address.to_string());
+
+        let mut closed = true;
+        for member in &group {
+            let (member_given, member_received) = rating_edges(deps, member, graph_start)?;
+            let member_partners = mutual_high_partners(&member_given, &member_received);
+
+            let counterparts_inside = member_given
+                .keys()
+                .chain(member_received.keys())
+                .all(|counterpart| group.contains(counterpart));
+            let rates_whole_group = group
+                .iter()
+                .filter(|other| *other != member)
+                .all(|other| member_partners.contains(other));
+
+            if !counterparts_inside || !rates_whole_group {
+                closed = false;
+                break;
+            }
+        }
+
+        if closed {
+            is_suspicious = true;
+            confidence_score += 0.4;
+            evidence.push(format!("Closed rating clique: {} agents rating only each other", group_size));
+        }
+    }
+
+    // Check for rating bursts between newly registered agents
+    let recent_counterparts: Vec<String> = ratings_given_since(deps, agent_address.as_str(), window_start)?
+        .into_iter()
+        .map(|stored| stored.rating.rated_address.to_string())
+        .chain(
+            ratings_received_since(deps, agent_address.as_str(), window_start)?
+                .into_iter()
+                .map(|stored| stored.rating.rater_address.to_string()),
+        )
+        .collect();
+
+    if recent_counterparts.len() as u32 >= RATING_BURST_THRESHOLD {
+        let registry = CONFIG.load(deps.storage)?.agent_registry;
+        let registered_since = current_time.minus_seconds(NEW_AGENT_WINDOW);
+        let mut is_new = BTreeMap::new();
+        let mut check_new = |address: &str| -> StdResult<bool> {
+            if let Some(new) = is_new.get(address) {
+                return Ok(*new);
+            }
+            let new = query_registered_agent(deps, &registry, address)?
+                .is_some_and(|agent| agent.registration_date >= registered_since);
+            is_new.insert(address.to_string(), new);
+            Ok(new)
+        };
+
+        if check_new(agent_address.as_str())? {
+            let mut burst_count = 0u32;
+            let mut burst_members = BTreeSet::new();
+            for counterpart in &recent_counterparts {
+                if check_new(counterpart)? {
+                    burst_count += 1;
+                    burst_members.insert(counterpart.clone());
+                }
+            }
+
+            if burst_count >= RATING_BURST_THRESHOLD {
+                is_suspicious = true;
+                confidence_score += 0.3;
+                evidence.push(format!(
+                    "Rating burst between new agents: {} ratings in 1 hour",
+                    burst_count
+                ));
+                members.extend(burst_members);
+            }
+        }
+    }
+
+    if is_suspicious {
+        members.insert(agent_address.to_string());
+        evidence.push(format!(
+            "Ring members: {}",
+            members.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    let recommended_penalty = if is_suspicious {
+        Uint128::from(KARMA_PENALTY_MULTIPLIER * 3 * (confidence_score * 100.0) as u128)
+    } else {
+        Uint128::zero()
+    };
+
+    Ok(AbuseDetectionResult {
+        is_suspicious,
+        violation_type: if is_suspicious { Some(ViolationType::CollusionRing) } else { None },
+        confidence_score,
+        evidence,
+        recommended_penalty,
+    })
+}
+
 /// Apply karma penalty for detected abuse
 pub fn apply_abuse_penalty(
     deps: DepsMut,
     env: &Env,
     agent_address: &Addr,
+    violation_id: &str,
     violation: &ComplianceViolation,
 ) -> Result<(), ContractError> {
     // Load current karma score
     let mut karma_score = KARMA_SCORES
         .may_load(deps.storage, agent_address.as_str())?
         .unwrap_or_default();
+    let before = karma_score.current_score;
     
     // Apply penalty
     let penalty_amount = violation.penalty_applied;
@@ -355,80 +607,58 @@ pub fn apply_abuse_penalty(
     
     // Save updated karma score
     KARMA_SCORES.save(deps.storage, agent_address.as_str(), &karma_score)?;
+    let posting = Posting::new(KarmaLedgerReason::CompliancePenalty, None, Some(violation_id));
+    record_karma_movement(deps.storage, env, agent_address.as_str(), before, karma_score.current_score, posting)?;
     
     // Record penalty in tracking
-    let penalty_key = format!("{}:{}", agent_address, env.block.time.seconds());
-    KARMA_PENALTIES.save(deps.storage, &penalty_key, &penalty_amount)?;
+    KARMA_PENALTIES.save(deps.storage, violation_id, &penalty_amount)?;
     
     Ok(())
 }
 
-/// Check reputation-based rate limiting
+/// Violation ID made of `prefix` and a sequence number, so violations
+/// recorded within one block never share an ID
+pub fn next_violation_id(storage: &mut dyn Storage, prefix: &str) -> StdResult<String> {
+    let sequence = VIOLATION_COUNTER.may_load(storage)?.unwrap_or(0) + 1;
+    VIOLATION_COUNTER.save(storage, &sequence)?;
+    Ok(format!("{}:{}", prefix, sequence))
+}
+
+/// Store a new violation and file it under its agent
+pub fn record_violation(
+    storage: &mut dyn Storage,
+    violation_id: &str,
+    violation: &ComplianceViolation,
+) -> StdResult<()> {
+    COMPLIANCE_VIOLATIONS.save(storage, violation_id, violation)?;
+    AGENT_VIOLATIONS.save(storage, (violation.agent_address.as_str(), violation_id), &violation.timestamp)
+}
+
+/// Count one action against the agent's sliding-window limit for its type,
+/// returning false once the limit is reached
 pub fn check_rate_limit(
     deps: DepsMut,
     env: &Env,
     agent_address: &Addr,
     action_type: &str,
 ) -> Result<bool, ContractError> {
-    let current_time = env.block.time;
-    let window_start = current_time.minus_seconds(RATING_PATTERN_WINDOW);
-    
-    // Get current karma score for reputation-based limits
-    let karma_score = KARMA_SCORES
-        .may_load(deps.storage, agent_address.as_str())?
-        .unwrap_or_default();
-    
-    // Calculate rate limit based on karma (higher karma = higher limits)
-    let base_limit = match action_type {
-        "rating" => SPAM_RATING_THRESHOLD,
-        "interaction" => BOT_BEHAVIOR_THRESHOLD,
-        _ => 20,
-    };
-    
-    let karma_multiplier = if karma_score.current_score.u128() > 1000 {
-        2.0
-    } else if karma_score.current_score.u128() > 500 {
-        1.5
-    } else if karma_score.current_score.u128() > 100 {
-        1.2
-    } else {
-        1.0
-    };
-    
-    let effective_limit = (base_limit as f64 * karma_multiplier) as u32;
-    
-    // Get or create rate limit tracker
-    let tracker_key = agent_address.as_str();
-    let mut tracker = RATE_LIMIT_TRACKERS
-        .may_load(deps.storage, tracker_key)?
-        .unwrap_or(RateLimitTracker {
-            agent_address: agent_address.clone(),
-            action_type: action_type.to_string(),
-            count: 0,
-            window_start: current_time,
-            last_action: current_time,
-        });
-    
-    // Reset counter if window has passed
-    if current_time.seconds() - tracker.window_start.seconds() > RATING_PATTERN_WINDOW {
-        tracker.count = 0;
-        tracker.window_start = current_time;
-    }
-    
-    // Check if limit would be exceeded
-    if tracker.count >= effective_limit {
-        return Ok(false); // Rate limit exceeded
-    }
-    
-    // Update tracker
-    tracker.count += 1;
-    tracker.last_action = current_time;
-    RATE_LIMIT_TRACKERS.save(deps.storage, tracker_key, &tracker)?;
-    
-    Ok(true) // Rate limit OK
+    let config = rate_limit_config(deps.storage)?;
+    let granted = consume_quota(
+        deps.storage,
+        &config,
+        agent_address.as_str(),
+        action_type,
+        Uint128::one(),
+        env.block.time,
+    )?;
+    
+    Ok(!granted.is_zero())
 }
 
 /// Create a dispute case for a compliance violation
+///
+/// A violation can only be contested while it stands and no other case is
+/// open against it.
 pub fn create_dispute(
     deps: DepsMut,
     env: &Env,
@@ -449,8 +679,27 @@ pub fn create_dispute(
         });
     }
     
+    let mut violation = COMPLIANCE_VIOLATIONS
+        .may_load(deps.storage, &violation_id)?
+        .ok_or_else(|| ContractError::ViolationNotFound {
+            violation_id: violation_id.clone(),
+        })?;
+    if violation.overturned {
+        return Err(ContractError::ViolationAlreadyOverturned { violation_id });
+    }
+    if let Some(case_id) = violation.open_dispute {
+        return Err(ContractError::ViolationUnderDispute { violation_id, case_id });
+    }
+    
     // Generate unique case ID
-    let case_id = format!("dispute_{}_{}", violation_id, env.block.time.seconds());
+    let sequence = DISPUTE_COUNTER.may_load(deps.storage)?.unwrap_or(0) + 1;
+    DISPUTE_COUNTER.save(deps.storage, &sequence)?;
+    let case_id = format!("dispute_{}_{}", violation_id, sequence);
+
+    // Mark the violation as contested
+    violation.disputed = true;
+    violation.open_dispute = Some(case_id.clone());
+    COMPLIANCE_VIOLATIONS.save(deps.storage, &violation_id, &violation)?;
     
     // Create dispute case
     let dispute_case = DisputeCase {
@@ -463,29 +712,66 @@ pub fn create_dispute(
         created_at: env.block.time,
         resolved_at: None,
         resolution: None,
+        juror_draw_height: None,
     };
     
     // Save dispute case
     DISPUTE_CASES.save(deps.storage, &case_id, &dispute_case)?;
+    VIOLATION_DISPUTES.save(deps.storage, (dispute_case.violation_id.as_str(), case_id.as_str()), &())?;
     
     // Deduct stake from challenger's karma
     let mut updated_karma = challenger_karma;
+    let before = updated_karma.current_score;
     updated_karma.current_score = updated_karma.current_score.saturating_sub(stake_amount);
     KARMA_SCORES.save(deps.storage, challenger.as_str(), &updated_karma)?;
+    let posting = Posting::new(KarmaLedgerReason::DisputeStake, None, Some(&case_id));
+    record_karma_movement(deps.storage, env, challenger.as_str(), before, updated_karma.current_score, posting)?;
     
     Ok(case_id)
 }
 
+/// File up to `limit` more disputes filed before they were kept per
+/// violation under their violation; returns the number of disputes filed and
+/// whether every dispute was reached
+pub fn migrate_dispute_index(storage: &mut dyn Storage, limit: u32) -> StdResult<(u32, bool)> {
+    let start_after = DISPUTE_INDEX_MIGRATION.may_load(storage)?;
+    let cases = DISPUTE_CASES
+        .range(storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit as usize + 1)
+        .map(|item| item.map(|(case_id, dispute_case)| (case_id, dispute_case.violation_id)))
+        .collect::<StdResult<Vec<_>>>()?;
+    let completed = cases.len() <= limit as usize;
+
+    let mut indexed = 0;
+    for (case_id, violation_id) in cases.into_iter().take(limit as usize) {
+        let key = (violation_id.as_str(), case_id.as_str());
+        if !VIOLATION_DISPUTES.has(storage, key) {
+            VIOLATION_DISPUTES.save(storage, key, &())?;
+            indexed += 1;
+        }
+        DISPUTE_INDEX_MIGRATION.save(storage, &case_id)?;
+    }
+
+    Ok((indexed, completed))
+}
+
 /// Resolve a dispute case
+///
+/// Overturning a violation returns the challenger's stake, refunds the
+/// penalty and rewards the challenger out of the forfeited-stake pool; a
+/// partial overturn returns `PARTIAL_OVERTURN_PERCENT` of both stake and
+/// penalty. Stake that is not returned is forfeited to the pool. A violation
+/// is only overturned once, so at most one reward is paid for it. Karma of the
+/// refunded agent still needs recalculating, and the challenger relisting.
 pub fn resolve_dispute(
     deps: DepsMut,
     env: &Env,
     case_id: &str,
     resolution: DisputeResolution,
-) -> Result<(), ContractError> {
+) -> Result<DisputeOutcome, ContractError> {
     let mut dispute_case = DISPUTE_CASES.load(deps.storage, case_id)?;
     
-    if dispute_case.status != DisputeStatus::Pending {
+    if !matches!(dispute_case.status, DisputeStatus::Pending | DisputeStatus::UnderReview) {
         return Err(ContractError::DisputeAlreadyResolved {
             case_id: case_id.to_string(),
         });
@@ -496,36 +782,116 @@ pub fn resolve_dispute(
     dispute_case.resolved_at = Some(env.block.time);
     dispute_case.resolution = Some(resolution.clone());
     
-    // Handle resolution
-    match resolution {
-        DisputeResolution::ViolationOverturned => {
-            // Return stake to challenger and reverse penalty
-            let mut challenger_karma = KARMA_SCORES
-                .load(deps.storage, dispute_case.challenger.as_str())?;
-            challenger_karma.current_score += dispute_case.stake_amount;
-            KARMA_SCORES.save(deps.storage, dispute_case.challenger.as_str(), &challenger_karma)?;
-            
-            // TODO: Reverse the original violation penalty
-        }
-        DisputeResolution::ViolationConfirmed => {
-            // Stake is forfeited (already deducted)
+    // The violation is no longer contested, whatever the outcome
+    let mut violation = COMPLIANCE_VIOLATIONS.may_load(deps.storage, &dispute_case.violation_id)?;
+    if let Some(violation) = violation.as_mut() {
+        violation.open_dispute = None;
+        COMPLIANCE_VIOLATIONS.save(deps.storage, &dispute_case.violation_id, violation)?;
+    }
+    
+    // Share of stake and penalty returned, and reward rate; only overturning a
+    // standing violation is rewarded
+    let (returned_percent, reward_percent) = match resolution {
+        DisputeResolution::ViolationOverturned => (100, DISPUTE_REWARD_PERCENT),
+        DisputeResolution::PartialOverturned => (PARTIAL_OVERTURN_PERCENT, 0),
+        DisputeResolution::ViolationConfirmed => (0, 0),
+    };
+    let reward_percent = match &violation {
+        Some(violation) if !violation.overturned => reward_percent,
+        _ => 0,
+    };
+    let mut outcome = DisputeOutcome {
+        stake_returned: dispute_case.stake_amount.multiply_ratio(returned_percent, 100u128),
+        ..DisputeOutcome::default()
+    };
+    
+    // Forfeit the rest of the stake to the pool, then pay the reward out of it
+    let mut pool = FORFEITED_STAKE_POOL.may_load(deps.storage)?.unwrap_or_default();
+    pool += dispute_case.stake_amount - outcome.stake_returned;
+    outcome.reward = std::cmp::min(pool, dispute_case.stake_amount.multiply_ratio(reward_percent, 100u128));
+    pool -= outcome.reward;
+    FORFEITED_STAKE_POOL.save(deps.storage, &pool)?;
+    
+    let challenger = dispute_case.challenger.as_str();
+    let mut challenger_karma = KARMA_SCORES.may_load(deps.storage, challenger)?.unwrap_or_default();
+    for (amount, reason) in [
+        (outcome.stake_returned, KarmaLedgerReason::DisputeStakeReturn),
+        (outcome.reward, KarmaLedgerReason::DisputeReward),
+    ] {
+        if amount.is_zero() {
+            continue;
         }
-        DisputeResolution::PartialOverturned => {
-            // Return half the stake
-            let partial_return = dispute_case.stake_amount.checked_div(Uint128::from(2u128)).unwrap_or_default();
-            let mut challenger_karma = KARMA_SCORES
-                .load(deps.storage, dispute_case.challenger.as_str())?;
-            challenger_karma.current_score += partial_return;
-            KARMA_SCORES.save(deps.storage, dispute_case.challenger.as_str(), &challenger_karma)?;
+        let before = challenger_karma.current_score;
+        challenger_karma.current_score += amount;
+        KARMA_SCORES.save(deps.storage, challenger, &challenger_karma)?;
+        let posting = Posting::new(reason, None, Some(case_id));
+        record_karma_movement(deps.storage, env, challenger, before, challenger_karma.current_score, posting)?;
+    }
+    if !(outcome.stake_returned + outcome.reward).is_zero() {
+        outcome.challenger = Some(dispute_case.challenger.clone());
+    }
+    
+    // Refund the overturned share of the penalty
+    if let Some(mut violation) = violation.filter(|_| returned_percent > 0) {
+        // Karma already recovered in good standing counts towards the refund
+        let refund = violation
+            .penalty_applied
+            .multiply_ratio(returned_percent, 100u128)
+            .saturating_sub(violation.penalty_refunded + violation.penalty_recovered);
+        violation.penalty_refunded += refund;
+        violation.overturned |= resolution == DisputeResolution::ViolationOverturned;
+        COMPLIANCE_VIOLATIONS.save(deps.storage, &dispute_case.violation_id, &violation)?;
+        
+        let agent = violation.agent_address.as_str();
+        let mut karma_score = KARMA_SCORES.may_load(deps.storage, agent)?.unwrap_or_default();
+        let before = karma_score.current_score;
+        karma_score.current_score += refund;
+        KARMA_SCORES.save(deps.storage, agent, &karma_score)?;
+        let posting = Posting::new(
+            KarmaLedgerReason::PenaltyRefund,
+            Some(&dispute_case.challenger),
+            Some(&dispute_case.violation_id),
+        );
+        record_karma_movement(deps.storage, env, agent, before, karma_score.current_score, posting)?;
+        
+        // Take the refund off the recorded penalty
+        let penalty_key = dispute_case.violation_id.as_str();
+        if let Some(penalty) = KARMA_PENALTIES.may_load(deps.storage, penalty_key)? {
+            KARMA_PENALTIES.save(deps.storage, penalty_key, &penalty.saturating_sub(refund))?;
         }
+        
+        outcome.refunded_agent = Some(violation.agent_address);
+        outcome.penalty_refunded = refund;
     }
     
     // Save updated dispute case
     DISPUTE_CASES.save(deps.storage, case_id, &dispute_case)?;
     
-    Ok(())
+    Ok(outcome)
 }
 
+/// Parse a dispute resolution as given in messages: "confirmed",
+/// "overturned" or "partial"
+pub fn parse_dispute_resolution(resolution: &str) -> Result<DisputeResolution, ContractError> {
+    match resolution {
+        "confirmed" => Ok(DisputeResolution::ViolationConfirmed),
+        "overturned" => Ok(DisputeResolution::ViolationOverturned),
+        "partial" => Ok(DisputeResolution::PartialOverturned),
+        _ => Err(ContractError::ComplianceViolation {
+            reason: "Invalid resolution type".to_string(),
+        }),
+    }
+}
+
+/// Violation type reported by each detector `run_abuse_detection` runs, in
+/// the order of its results
+pub const DETECTORS: [ViolationType; 4] = [
+    ViolationType::SpamRating,
+    ViolationType::BotBehavior,
+    ViolationType::RatingManipulation,
+    ViolationType::CollusionRing,
+];
+
 /// Run comprehensive abuse detection on an agent
 pub fn run_abuse_detection(
     deps: Deps,
@@ -538,6 +904,7 @@ pub fn run_abuse_detection(
     results.push(detect_spam_ratings(deps, env, agent_address)?);
     results.push(detect_bot_behavior(deps, env, agent_address)?);
     results.push(detect_rating_manipulation(deps, env, agent_address)?);
+    results.push(detect_collusion_ring(deps, env, agent_address)?);
     
     Ok(results)
 }
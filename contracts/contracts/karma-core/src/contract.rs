@@ -1,30 +1,59 @@
 use cosmwasm_std::{
     entry_point, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response,
-    StdResult, Uint128,
+    Int128, StdError, StdResult, Storage, Timestamp, Uint128,
 };
 use cw2::set_contract_version;
 use cw_storage_plus::Bound;
+use std::collections::BTreeMap;
 
 use agent_karma_contracts::{
     messages::{karma_core::*, InstantiateMsg, MigrateMsg},
-    types::{KarmaConfig, OracleData, Rating},
+    types::{
+        ArbitrationConfig, EnforcementAction, EnforcementConfig, RateLimitConfig, RehabilitationConfig, HistoryInterval, KarmaAlgorithmConfig, KarmaConfig, KarmaLedgerReason, OracleData, Rating, RatingReply, RatingRevision,
+        TrustConfig, DEFAULT_DECAY_HALF_LIFE, DEFAULT_FEEDBACK_FLAG_THRESHOLD,
+        DEFAULT_MIN_KARMA_FOR_FLAGGING, DEFAULT_RATING_GRACE_PERIOD,
+    },
 };
 
+use crate::aggregates::{
+    defer_decay, migrate_rating_aggregates, record_category_rating, record_rating, revise_rating,
+};
+use crate::algorithm::validate_algorithm_config;
+use crate::arbitration::{
+    commit_vote, finalize_arbitration, open_arbitration, reveal_vote, schedule_juror_draw,
+    validate_arbitration_config, JurorDraw,
+};
 use crate::compliance::{
-    apply_abuse_penalty, check_rate_limit, create_dispute, resolve_dispute, run_abuse_detection,
-    DisputeResolution, ViolationType,
+    apply_abuse_penalty, backfill_rating_index, check_rate_limit, create_dispute, migrate_dispute_index,
+    next_violation_id, parse_dispute_resolution, parse_violation_type, record_violation, resolve_dispute, run_abuse_detection,
+    DisputeOutcome, DisputeStatus, ViolationType, DETECTORS,
 };
 use crate::error::ContractError;
+use crate::simulation::OverlayStorage;
+use crate::trust::{advance_trust_epoch, query_trust, validate_trust_config};
+use crate::history::{history_buckets, history_page, latest_history, migrate_legacy_history};
+use crate::ledger::{ledger_page, migrate_legacy_karma_balance, record_karma_movement, Posting};
+use crate::rate_limit::{quota, rate_limit_config, validate_rate_limit_config, EARNING_ACTION};
+use crate::rehabilitation::{
+    agent_violations, is_violation_active, migrate_violation_index, rehabilitate_agent, validate_rehabilitation_config,
+    violation_expires_at,
+};
+use crate::enforcement::{
+    action_name, enforce_detection_result, rating_suspension, validate_enforcement_config,
+};
+use crate::oracle::{migrate_legacy_oracle_data, record_oracle_data};
 use crate::helpers::{
     apply_karma_penalty, check_karma_requirement, check_minimum_requirements,
-    earn_karma_from_rating, generate_rating_id, get_agent_karma_score, spend_karma,
-    validate_interaction_hash,
+    earn_karma_from_rating, generate_rating_id, get_agent_karma_score, query_logged_interaction,
+    query_is_registered, query_registered_agent, refund_karma, reverse_rating_karma, spend_karma,
+    validate_interaction_hash, verify_agent_registered,
 };
 use crate::karma::{
-    calculate_karma_score, update_karma_score, validate_rating_score, validate_rating_window,
-    validate_rating_window_with_hash,
+    calculate_karma_score, compute_category_karma, compute_karma, project_karma_score,
+    update_karma_score,
+    validate_rating_score, validate_rating_window,
 };
-use crate::state::{ratings, Config, RatingTracker, StoredRating, CONFIG, KARMA_HISTORY, KARMA_SCORES, LEADERBOARD, ORACLE_DATA, RATING_COUNTER, RATING_TRACKERS, COMPLIANCE_VIOLATIONS, DISPUTE_CASES, RATE_LIMIT_TRACKERS};
+use crate::state::{ratings, Config, DetectorRun, ABUSE_PATTERNS, KarmaScore, RatingTracker, StoredRating, CATEGORY_KARMA, RETRACTED_RATINGS, FROZEN_AGENTS, CATEGORY_LEADERBOARD, CATEGORY_LEADERBOARD_SCORES, CONFIG, KARMA_ADJUSTMENTS, KARMA_SCORES, LEADERBOARD, LEADERBOARD_REBUILD, LEADERBOARD_SCORES, LEADERBOARD_SIZE, LEGACY_LEADERBOARD, RATING_COUNTER, RATING_TRACKERS, COMPLIANCE_VIOLATIONS, DISPUTE_CASES, VIOLATION_DISPUTES, TRUST_CREDIBILITY, TRUST_EPOCH, ARBITRATIONS, JUROR_VOTES};
 
 // Contract name and version for migration
 const CONTRACT_NAME: &str = "karma-core";
@@ -37,6 +66,24 @@ const DEFAULT_MIN_KARMA_FOR_PROPOSAL: u128 = 100;
 const DEFAULT_RATING_WINDOW: u64 = 24 * 60 * 60; // 24 hours in seconds
 const DEFAULT_MAX_RATINGS_PER_INTERACTION: u8 = 1;
 const DEFAULT_RATING_FEE: u128 = 2;
+const DEFAULT_HISTORY_RETENTION: u64 = 90 * 24 * 60 * 60; // 90 days in seconds
+
+// Longest reply a rated agent may post to a rating (in bytes)
+const MAX_RATING_REPLY_LENGTH: usize = 1000;
+
+// Leaderboard entries looked up in the agent registry per page
+const MAX_LEADERBOARD_SCAN: usize = 200;
+
+/// Leaderboard positions `GetAgentRank` counts exactly
+const MAX_RANK_SCAN: usize = 1000;
+
+// Node and edge visits per `AdvanceTrustEpoch` call
+const DEFAULT_TRUST_STEPS: u32 = 100;
+const MAX_TRUST_STEPS: u32 = 1000;
+
+// Entries processed per `BackfillRatingIndex` or `RebuildLeaderboard` call
+const DEFAULT_BACKFILL_LIMIT: u32 = 100;
+const MAX_BACKFILL_LIMIT: u32 = 500;
 
 #[entry_point]
 pub fn instantiate(
@@ -60,7 +107,27 @@ pub fn instantiate(
         rating_window: DEFAULT_RATING_WINDOW,
         max_ratings_per_interaction: DEFAULT_MAX_RATINGS_PER_INTERACTION,
         rating_fee: Uint128::from(DEFAULT_RATING_FEE),
+        algorithm: KarmaAlgorithmConfig::default(),
+        decay_half_life: DEFAULT_DECAY_HALF_LIFE,
+        history_retention: DEFAULT_HISTORY_RETENTION,
+        trust: TrustConfig {
+            pre_trusted: vec![admin.to_string()],
+            ..TrustConfig::default()
+        },
+        rating_grace_period: DEFAULT_RATING_GRACE_PERIOD,
+        min_karma_for_flagging: Uint128::new(DEFAULT_MIN_KARMA_FOR_FLAGGING),
+        feedback_flag_threshold: DEFAULT_FEEDBACK_FLAG_THRESHOLD,
+        arbitration: ArbitrationConfig::default(),
+        rate_limits: RateLimitConfig::default(),
+        enforcement: EnforcementConfig::default(),
+        rehabilitation: RehabilitationConfig::default(),
     });
+    validate_algorithm_config(&karma_config.algorithm)?;
+    validate_trust_config(deps.api, &karma_config.trust)?;
+    validate_arbitration_config(&karma_config.arbitration)?;
+    validate_rate_limit_config(&karma_config.rate_limits)?;
+    validate_enforcement_config(&karma_config.enforcement)?;
+    validate_rehabilitation_config(&karma_config.rehabilitation)?;
 
     let config = Config {
         admin: admin.clone(),
@@ -101,6 +168,18 @@ pub fn execute(
             feedback,
             interaction_hash,
         ),
+        ExecuteMsg::AmendRating {
+            rating_id,
+            score,
+            feedback,
+        } => execute_amend_rating(deps, env, info, rating_id, score, feedback),
+        ExecuteMsg::RetractRating { rating_id } => {
+            execute_retract_rating(deps, env, info, rating_id)
+        }
+        ExecuteMsg::RespondToRating { rating_id, reply } => {
+            execute_respond_to_rating(deps, env, info, rating_id, reply)
+        }
+        ExecuteMsg::FlagFeedback { rating_id } => execute_flag_feedback(deps, info, rating_id),
         ExecuteMsg::RecalculateKarma { agent_address } => {
             execute_recalculate_karma(deps, env, info, agent_address)
         }
@@ -126,6 +205,9 @@ pub fn execute(
             severity,
             evidence,
         ),
+        ExecuteMsg::RehabilitateAgent { agent_address } => {
+            execute_rehabilitate_agent(deps, env, agent_address)
+        }
         ExecuteMsg::CreateDispute {
             violation_id,
             stake_amount,
@@ -135,11 +217,37 @@ pub fn execute(
             case_id,
             resolution,
         } => execute_resolve_dispute(deps, env, info, case_id, resolution),
+        ExecuteMsg::CommitJurorVote {
+            case_id,
+            commitment,
+        } => execute_commit_juror_vote(deps, env, info, case_id, commitment),
+        ExecuteMsg::RevealJurorVote {
+            case_id,
+            resolution,
+            salt,
+        } => execute_reveal_juror_vote(deps, env, info, case_id, resolution, salt),
+        ExecuteMsg::DrawJurors { case_id } => execute_draw_jurors(deps, env, case_id),
+        ExecuteMsg::FinalizeArbitration { case_id } => execute_finalize_arbitration(deps, env, case_id),
+        ExecuteMsg::AdvanceTrustEpoch { limit } => execute_advance_trust_epoch(deps, limit),
+        ExecuteMsg::BackfillRatingIndex { limit } => execute_backfill_rating_index(deps, limit),
+        ExecuteMsg::RebuildLeaderboard { limit } => execute_rebuild_leaderboard(deps, limit),
+        ExecuteMsg::MigrateKarmaHistory { limit } => execute_migrate_karma_history(deps, limit),
+        ExecuteMsg::MigrateKarmaLedgers { limit } => execute_migrate_karma_ledgers(deps, env, limit),
+        ExecuteMsg::MigrateViolationIndex { limit } => execute_migrate_violation_index(deps, limit),
+        ExecuteMsg::MigrateDisputeIndex { limit } => execute_migrate_dispute_index(deps, limit),
+        ExecuteMsg::MigrateRatingAggregates { limit } => execute_migrate_rating_aggregates(deps, limit),
+        ExecuteMsg::UpdateContractAddresses {
+            agent_registry,
+            interaction_logger,
+        } => execute_update_contract_addresses(deps, info, agent_registry, interaction_logger),
+        ExecuteMsg::SyncAgentStatus { agent_address } => {
+            execute_sync_agent_status(deps, env, agent_address)
+        }
     }
 }
 
 pub fn execute_submit_rating(
-    mut deps: DepsMut,
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
     rated_agent: String,
@@ -147,47 +255,130 @@ pub fn execute_submit_rating(
     feedback: Option<String>,
     interaction_hash: String,
 ) -> Result<Response, ContractError> {
+    let outcome = submit_rating(
+        deps,
+        &env,
+        &info.sender,
+        &rated_agent,
+        score,
+        feedback,
+        &interaction_hash,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "submit_rating")
+        .add_attribute("rater", info.sender)
+        .add_attribute("rated_agent", rated_agent)
+        .add_attribute("score", score.to_string())
+        .add_attribute("interaction_hash", interaction_hash)
+        .add_attribute("rating_id", outcome.rating_id)
+        .add_attribute("new_karma", outcome.new_karma)
+        .add_attribute("fee_paid", outcome.fee_paid)
+        .add_attribute("karma_earned", outcome.karma_earned)
+        .add_attribute("karma_penalty", outcome.karma_penalty))
+}
+
+/// Effects of an accepted rating
+pub struct RatingOutcome {
+    pub rating_id: String,
+    pub new_karma: Uint128,
+    pub fee_paid: Uint128,
+    pub karma_earned: Uint128,
+    pub karma_penalty: Uint128,
+}
+
+/// Validate a rating and apply it: fee, karma earning/penalty and recalculation
+///
+/// Shared by `SubmitRating` and the `SimulateRating` dry run.
+fn submit_rating(
+    mut deps: DepsMut,
+    env: &Env,
+    rater: &Addr,
+    rated_agent: &str,
+    score: u8,
+    feedback: Option<String>,
+    interaction_hash: &str,
+) -> Result<RatingOutcome, ContractError> {
     let config = CONFIG.load(deps.storage)?;
-    let rater = info.sender.clone();
-    let rated_agent_addr = deps.api.addr_validate(&rated_agent)?;
+    let rated_agent_addr = deps.api.addr_validate(rated_agent)?;
 
     // Validate inputs
     validate_rating_score(score)?;
-    validate_interaction_hash(&interaction_hash)?;
+    validate_interaction_hash(interaction_hash)?;
 
     // Check if rater is trying to rate themselves
-    if rater == rated_agent_addr {
+    if *rater == rated_agent_addr {
         return Err(ContractError::CannotRateSelf {});
     }
 
+    // Both agents must be registered and active
+    verify_agent_registered(deps.as_ref(), rater)?;
+    if !sync_agent_status(deps.branch(), env, &rated_agent_addr)? {
+        return Err(ContractError::AgentNotFound {
+            address: rated_agent_addr.to_string(),
+        });
+    }
+
+    // Agents suspended by an enforcement policy cannot rate
+    if let Some(until) = rating_suspension(deps.storage, rater.as_str(), env.block.time)? {
+        return Err(ContractError::RatingRightsSuspended {
+            address: rater.to_string(),
+            until: until.seconds(),
+        });
+    }
+
     // Check minimum karma requirements for rating
-    check_minimum_requirements(deps.as_ref(), &rater, "rating")?;
+    check_minimum_requirements(deps.as_ref(), rater, "rating")?;
 
     // Check rate limiting for rating submissions
-    if !check_rate_limit(deps.branch(), &env, &rater, "rating")? {
+    if !check_rate_limit(deps.branch(), env, rater, "rating")? {
         return Err(ContractError::RateLimitExceeded {
             action: "rating".to_string(),
         });
     }
 
-    // Check for duplicate ratings
-    let tracker_key = (interaction_hash.as_str(), rater.as_str());
-    if RATING_TRACKERS.has(deps.storage, tracker_key) {
-        return Err(ContractError::RatingAlreadySubmitted {
-            interaction_hash: interaction_hash.clone(),
-        });
+    // The rated interaction must be logged, with both agents taking part
+    let interaction =
+        query_logged_interaction(deps.as_ref(), &config.interaction_logger, interaction_hash)?
+            .ok_or_else(|| ContractError::InteractionNotFound {
+                interaction_hash: interaction_hash.to_string(),
+            })?;
+    for participant in [rater, &rated_agent_addr] {
+        if !interaction.participants.contains(participant) {
+            return Err(ContractError::NotInteractionParticipant {
+                address: participant.to_string(),
+                interaction_hash: interaction_hash.to_string(),
+            });
+        }
     }
 
-    // Validate rating window (24 hours) with enhanced validation
-    validate_rating_window_with_hash(
-        deps.as_ref(),
-        &interaction_hash,
+    // Check for duplicate ratings and the per-interaction limit
+    let tracker_key = (interaction_hash, rater.as_str());
+    let tracker = RATING_TRACKERS.may_load(deps.storage, tracker_key)?;
+    if let Some(tracker) = &tracker {
+        if tracker.rated_agents.contains(&rated_agent_addr) {
+            return Err(ContractError::RatingAlreadySubmitted {
+                interaction_hash: interaction_hash.to_string(),
+            });
+        }
+        if tracker.rating_count() >= config.karma_config.max_ratings_per_interaction as usize {
+            return Err(ContractError::MaxRatingsPerInteractionReached {
+                interaction_hash: interaction_hash.to_string(),
+                max: config.karma_config.max_ratings_per_interaction,
+            });
+        }
+    }
+
+    // Ratings must fall within the rating window of the logged interaction
+    validate_rating_window(
+        interaction_hash,
+        &interaction.timestamp,
         &env.block.time,
         config.karma_config.rating_window,
     )?;
 
-    // Charge rating fee
-    spend_karma(deps.branch(), &rater, config.karma_config.rating_fee)?;
+    // Ratings count towards the karma of the rated interaction's type
+    let interaction_type = Some(interaction.interaction_type);
 
     // Generate unique rating ID
     let rating_counter = RATING_COUNTER.load(deps.storage)?;
@@ -195,12 +386,16 @@ pub fn execute_submit_rating(
     RATING_COUNTER.save(deps.storage, &new_counter)?;
 
     let rating_id = generate_rating_id(
-        &rater,
+        rater,
         &rated_agent_addr,
-        &interaction_hash,
+        interaction_hash,
         env.block.time.seconds(),
     );
 
+    // Charge rating fee
+    let posting = Posting::new(KarmaLedgerReason::RatingFee, Some(&rated_agent_addr), Some(&rating_id));
+    spend_karma(deps.branch(), env, rater, config.karma_config.rating_fee, posting)?;
+
     // Create rating
     let rating = Rating {
         id: rating_id.clone(),
@@ -208,54 +403,315 @@ pub fn execute_submit_rating(
         rated_address: rated_agent_addr.clone(),
         score,
         feedback,
-        interaction_hash: interaction_hash.clone(),
+        interaction_hash: interaction_hash.to_string(),
         timestamp: env.block.time,
         block_height: env.block.height,
     };
 
+    let rater_karma = get_agent_karma_score(deps.as_ref(), rater)?;
+
+    // Apply karma earning/penalty based on rating score
+    let karma_earned = earn_karma_from_rating(deps.branch(), env, &rating, rater_karma)?;
+    let karma_penalty = apply_karma_penalty(deps.branch(), env, &rating)?;
+
     let stored_rating = StoredRating {
         rating,
         processed: false,
         fee_paid: config.karma_config.rating_fee,
+        interaction_type,
+        rater_karma: Some(rater_karma),
+        revisions: vec![],
+        retracted_at: None,
+        reply: None,
+        feedback_flags: vec![],
+        karma_awarded: karma_earned,
+        karma_penalized: karma_penalty,
     };
 
-    // Save rating
+    // Fold the rating into the rated agent's aggregates, then save it
+    record_rating(deps.storage, &stored_rating.rating, rater_karma)?;
     ratings().save(deps.storage, &rating_id, &stored_rating)?;
 
-    // Create duplicate prevention tracker
-    let tracker = RatingTracker {
-        interaction_hash: interaction_hash.clone(),
+    // Create or extend the duplicate prevention tracker
+    let mut tracker = tracker.unwrap_or_else(|| RatingTracker {
+        interaction_hash: interaction_hash.to_string(),
         rater: rater.clone(),
         submitted_at: env.block.time,
-    };
+        rated_agents: vec![],
+    });
+    tracker.submitted_at = env.block.time;
+    tracker.rated_agents.push(rated_agent_addr.clone());
     RATING_TRACKERS.save(deps.storage, tracker_key, &tracker)?;
 
-    // Apply karma earning/penalty based on rating score
-    let rater_karma = get_agent_karma_score(deps.as_ref(), &rater)?;
-    let karma_earned =
-        earn_karma_from_rating(deps.branch(), &rated_agent_addr, score, rater_karma)?;
-    let karma_penalty = apply_karma_penalty(deps.branch(), &rated_agent_addr, score)?;
-
     // Recalculate karma for the rated agent
-    let karma_calculation = calculate_karma_score(deps.as_ref(), &env, &rated_agent_addr)?;
-    update_karma_score(deps.branch(), &env, &rated_agent_addr, &karma_calculation)?;
+    let karma_calculation = calculate_karma_score(deps.as_ref(), env, &rated_agent_addr)?;
+    let new_karma = update_karma_score(deps.branch(), env, &rated_agent_addr, &karma_calculation)?;
 
     // Update leaderboard
-    update_leaderboard(
-        deps.branch(),
-        &rated_agent_addr,
-        karma_calculation.current_score,
-    )?;
+    update_leaderboard(deps.branch(), &rated_agent_addr, new_karma)?;
+
+    if let Some(interaction_type) = &stored_rating.interaction_type {
+        update_category_karma(
+            deps.branch(),
+            env,
+            &stored_rating.rating,
+            interaction_type,
+            rater_karma,
+        )?;
+    }
+
+    Ok(RatingOutcome {
+        rating_id,
+        new_karma,
+        fee_paid: config.karma_config.rating_fee,
+        karma_earned,
+        karma_penalty,
+    })
+}
+
+pub fn execute_amend_rating(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rating_id: String,
+    score: u8,
+    feedback: Option<String>,
+) -> Result<Response, ContractError> {
+    validate_rating_score(score)?;
+    let previous = load_revisable_rating(deps.as_ref(), &env, &info.sender, &rating_id)?;
+    let rated_agent = previous.rating.rated_address.clone();
+
+    // Like new ratings, amendments need the rated agent to be active
+    if !sync_agent_status(deps.branch(), &env, &rated_agent)? {
+        return Err(ContractError::AgentNotFound {
+            address: rated_agent.to_string(),
+        });
+    }
+
+    // The fee paid for the rating covers its amendments
+    let mut amended = previous.clone();
+    amended.revisions.push(RatingRevision {
+        score: previous.rating.score,
+        feedback: previous.rating.feedback.clone(),
+        replaced_at: env.block.time,
+    });
+    amended.rating.score = score;
+    amended.rating.feedback = feedback;
+
+    // Swap the earning/penalty of the previous score for the amended one's,
+    // weighed by the rater's karma when the rating was first given
+    reverse_rating_karma(deps.branch(), &env, &previous)?;
+    let rater_karma = match previous.rater_karma {
+        Some(rater_karma) => rater_karma,
+        None => get_agent_karma_score(deps.as_ref(), &info.sender)?,
+    };
+    amended.karma_awarded = earn_karma_from_rating(deps.branch(), &env, &amended.rating, rater_karma)?;
+    amended.karma_penalized = apply_karma_penalty(deps.branch(), &env, &amended.rating)?;
+    ratings().save(deps.storage, &rating_id, &amended)?;
+
+    let new_karma = revise_rated_karma(deps.branch(), &env, &previous, Some(&amended.rating))?;
 
     Ok(Response::new()
-        .add_attribute("method", "submit_rating")
-        .add_attribute("rater", rater)
+        .add_attribute("method", "amend_rating")
+        .add_attribute("rating_id", rating_id)
         .add_attribute("rated_agent", rated_agent)
+        .add_attribute("previous_score", previous.rating.score.to_string())
         .add_attribute("score", score.to_string())
-        .add_attribute("interaction_hash", interaction_hash)
+        .add_attribute("revision", amended.revisions.len().to_string())
+        .add_attribute("new_karma", new_karma))
+}
+
+pub fn execute_retract_rating(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rating_id: String,
+) -> Result<Response, ContractError> {
+    let previous = load_revisable_rating(deps.as_ref(), &env, &info.sender, &rating_id)?;
+    let rated_agent = previous.rating.rated_address.clone();
+
+    // Retracted ratings leave the rating index but keep their history. They
+    // still count against the interaction's rating limit.
+    let mut retracted = previous.clone();
+    retracted.revisions.push(RatingRevision {
+        score: previous.rating.score,
+        feedback: previous.rating.feedback.clone(),
+        replaced_at: env.block.time,
+    });
+    retracted.retracted_at = Some(env.block.time);
+    ratings().remove(deps.storage, &rating_id)?;
+    RETRACTED_RATINGS.save(deps.storage, &rating_id, &retracted)?;
+
+    let posting = Posting::new(KarmaLedgerReason::RatingFeeRefund, Some(&rated_agent), Some(&rating_id));
+    refund_karma(deps.branch(), &env, &info.sender, previous.fee_paid, posting)?;
+    reverse_rating_karma(deps.branch(), &env, &previous)?;
+
+    let new_karma = revise_rated_karma(deps.branch(), &env, &previous, None)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "retract_rating")
         .add_attribute("rating_id", rating_id)
-        .add_attribute("new_karma", karma_calculation.current_score)
-        .add_attribute("fee_paid", config.karma_config.rating_fee))
+        .add_attribute("rated_agent", rated_agent)
+        .add_attribute("fee_refunded", previous.fee_paid)
+        .add_attribute("new_karma", new_karma))
+}
+
+/// Load a rating its rater may still amend or retract
+fn load_revisable_rating(
+    deps: Deps,
+    env: &Env,
+    rater: &Addr,
+    rating_id: &str,
+) -> Result<StoredRating, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let stored = load_rating(deps, rating_id)?;
+
+    if stored.rating.rater_address != *rater {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let grace_period_end = stored
+        .rating
+        .timestamp
+        .plus_seconds(config.karma_config.rating_grace_period);
+    if env.block.time > grace_period_end {
+        return Err(ContractError::RatingGracePeriodExpired {
+            rating_id: rating_id.to_string(),
+        });
+    }
+
+    Ok(stored)
+}
+
+/// Re-derive the rated agent's aggregates and karma after a rating was
+/// amended to `revised`, or retracted, and return its karma
+///
+/// Deactivated agents keep their karma frozen; only their aggregates follow.
+fn revise_rated_karma(
+    mut deps: DepsMut,
+    env: &Env,
+    previous: &StoredRating,
+    revised: Option<&Rating>,
+) -> Result<Uint128, ContractError> {
+    let agent = &previous.rating.rated_address;
+    let category_aggregates = revise_rating(deps.storage, previous, revised)?;
+
+    let new_karma = refresh_karma(deps.branch(), env, agent)?;
+    if FROZEN_AGENTS.has(deps.storage, agent.as_str()) {
+        return Ok(new_karma);
+    }
+
+    if let Some(interaction_type) = &previous.interaction_type {
+        let category_score = match category_aggregates {
+            Some(aggregates) => {
+                let karma =
+                    compute_category_karma(deps.as_ref(), env, agent, interaction_type, &aggregates)?;
+                CATEGORY_KARMA.save(deps.storage, (agent.as_str(), interaction_type), &karma)?;
+                karma.current_score
+            }
+            None => {
+                CATEGORY_KARMA.remove(deps.storage, (agent.as_str(), interaction_type));
+                Uint128::zero()
+            }
+        };
+        update_category_leaderboard(deps.storage, interaction_type, agent.as_str(), category_score)?;
+    }
+
+    Ok(new_karma)
+}
+
+pub fn execute_respond_to_rating(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    rating_id: String,
+    reply: String,
+) -> Result<Response, ContractError> {
+    let mut stored = load_rating(deps.as_ref(), &rating_id)?;
+
+    if stored.rating.rated_address != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if stored.reply.is_some() {
+        return Err(ContractError::RatingAlreadyAnswered { rating_id });
+    }
+
+    let text = reply.trim();
+    if text.is_empty() {
+        return Err(ContractError::InvalidRatingReply {
+            reason: "Reply cannot be empty".to_string(),
+        });
+    }
+    if text.len() > MAX_RATING_REPLY_LENGTH {
+        return Err(ContractError::InvalidRatingReply {
+            reason: format!("Reply exceeds {} bytes", MAX_RATING_REPLY_LENGTH),
+        });
+    }
+
+    stored.reply = Some(RatingReply {
+        rating_id: rating_id.clone(),
+        text: text.to_string(),
+        replied_at: env.block.time,
+    });
+    ratings().save(deps.storage, &rating_id, &stored)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "respond_to_rating")
+        .add_attribute("rating_id", rating_id)
+        .add_attribute("rated_agent", info.sender))
+}
+
+pub fn execute_flag_feedback(
+    deps: DepsMut,
+    info: MessageInfo,
+    rating_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut stored = load_rating(deps.as_ref(), &rating_id)?;
+
+    if stored.rating.feedback.is_none() {
+        return Err(ContractError::NoFeedbackToFlag { rating_id });
+    }
+    // Raters fix their own feedback by amending it
+    if stored.rating.rater_address == info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if stored.feedback_flags.contains(&info.sender) {
+        return Err(ContractError::FeedbackAlreadyFlagged {
+            rating_id,
+            address: info.sender.to_string(),
+        });
+    }
+
+    verify_agent_registered(deps.as_ref(), &info.sender)?;
+    check_minimum_requirements(deps.as_ref(), &info.sender, "flagging")?;
+
+    stored.feedback_flags.push(info.sender.clone());
+    ratings().save(deps.storage, &rating_id, &stored)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "flag_feedback")
+        .add_attribute("rating_id", rating_id)
+        .add_attribute("flagger", info.sender)
+        .add_attribute("flags", stored.feedback_flags.len().to_string())
+        .add_attribute(
+            "feedback_hidden",
+            feedback_hidden(&stored, config.karma_config.feedback_flag_threshold).to_string(),
+        ))
+}
+
+/// Load a rating from the rating index
+fn load_rating(deps: Deps, rating_id: &str) -> Result<StoredRating, ContractError> {
+    ratings()
+        .may_load(deps.storage, rating_id)?
+        .ok_or_else(|| ContractError::RatingNotFound {
+            rating_id: rating_id.to_string(),
+        })
+}
+
+/// Whether a rating's feedback was flagged often enough to be hidden
+fn feedback_hidden(stored: &StoredRating, threshold: u32) -> bool {
+    stored.feedback_flags.len() >= threshold as usize
 }
 
 pub fn execute_recalculate_karma(
@@ -266,22 +722,274 @@ pub fn execute_recalculate_karma(
 ) -> Result<Response, ContractError> {
     let agent_addr = deps.api.addr_validate(&agent_address)?;
 
+    // Deactivated agents keep their karma frozen
+    if !sync_agent_status(deps.branch(), &env, &agent_addr)? {
+        return Ok(Response::new()
+            .add_attribute("method", "recalculate_karma")
+            .add_attribute("agent_address", agent_address)
+            .add_attribute("frozen", "true"));
+    }
+
     // Calculate new karma score
     let karma_calculation = calculate_karma_score(deps.as_ref(), &env, &agent_addr)?;
 
     // Update karma score
-    update_karma_score(deps.branch(), &env, &agent_addr, &karma_calculation)?;
+    let new_karma = update_karma_score(deps.branch(), &env, &agent_addr, &karma_calculation)?;
 
     // Update leaderboard
-    update_leaderboard(deps.branch(), &agent_addr, karma_calculation.current_score)?;
+    update_leaderboard(deps.branch(), &agent_addr, new_karma)?;
 
     Ok(Response::new()
         .add_attribute("method", "recalculate_karma")
         .add_attribute("agent_address", agent_address)
-        .add_attribute("new_karma", karma_calculation.current_score)
+        .add_attribute("new_karma", new_karma)
         .add_attribute("previous_karma", karma_calculation.previous_score))
 }
 
+pub fn execute_advance_trust_epoch(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_TRUST_STEPS).min(MAX_TRUST_STEPS);
+
+    let progress = advance_trust_epoch(deps.storage, &config.karma_config.trust, limit as u64)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "advance_trust_epoch")
+        .add_attribute("epoch", progress.epoch.to_string())
+        .add_attribute("nodes_processed", progress.nodes_processed.to_string())
+        .add_attribute("completed", progress.completed.to_string()))
+}
+
+pub fn execute_backfill_rating_index(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = backfill_rating_index(deps.storage, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "backfill_rating_index")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_migrate_karma_history(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = migrate_legacy_history(deps.storage, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_karma_history")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_migrate_karma_ledgers(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = migrate_legacy_karma_balance(deps.storage, &env, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_karma_ledgers")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_migrate_violation_index(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = migrate_violation_index(deps.storage, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_violation_index")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_migrate_dispute_index(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = migrate_dispute_index(deps.storage, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_dispute_index")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_migrate_rating_aggregates(
+    deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT);
+
+    let (processed, completed) = migrate_rating_aggregates(deps.storage, limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_rating_aggregates")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", completed.to_string()))
+}
+
+pub fn execute_rebuild_leaderboard(
+    mut deps: DepsMut,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_BACKFILL_LIMIT).min(MAX_BACKFILL_LIMIT) as usize;
+
+    // List agents from their karma scores, resuming after the last one listed
+    let start_after = LEADERBOARD_REBUILD.may_load(deps.storage)?;
+    let mut scores = KARMA_SCORES
+        .range(deps.storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| item.map(|(agent, karma)| (agent, karma.current_score)))
+        .collect::<StdResult<Vec<_>>>()?;
+    let listed_all = scores.len() <= limit;
+    scores.truncate(limit);
+
+    let mut processed = 0;
+    for (agent, score) in scores {
+        if !FROZEN_AGENTS.has(deps.storage, &agent) {
+            update_leaderboard(deps.branch(), &Addr::unchecked(&agent), score)?;
+        }
+        LEADERBOARD_REBUILD.save(deps.storage, &agent)?;
+        processed += 1;
+    }
+
+    // Clear the score-keyed leaderboard it replaces
+    let mut legacy = LEGACY_LEADERBOARD
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let cleared_all = legacy.len() <= limit;
+    legacy.truncate(limit);
+    for score in legacy {
+        LEGACY_LEADERBOARD.remove(deps.storage, score);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "rebuild_leaderboard")
+        .add_attribute("processed", processed.to_string())
+        .add_attribute("completed", (listed_all && cleared_all).to_string()))
+}
+
+pub fn execute_update_contract_addresses(
+    deps: DepsMut,
+    info: MessageInfo,
+    agent_registry: Option<String>,
+    interaction_logger: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Only admin can rewire contract addresses
+    if info.sender != config.admin {
+        return Err(ContractError::AdminRequired {});
+    }
+
+    if let Some(agent_registry) = agent_registry {
+        config.agent_registry = deps.api.addr_validate(&agent_registry)?;
+    }
+    if let Some(interaction_logger) = interaction_logger {
+        config.interaction_logger = deps.api.addr_validate(&interaction_logger)?;
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_contract_addresses")
+        .add_attribute("agent_registry", config.agent_registry)
+        .add_attribute("interaction_logger", config.interaction_logger))
+}
+
+pub fn execute_sync_agent_status(
+    deps: DepsMut,
+    env: Env,
+    agent_address: String,
+) -> Result<Response, ContractError> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+    let active = sync_agent_status(deps, &env, &agent_addr)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "sync_agent_status")
+        .add_attribute("agent_address", agent_address)
+        .add_attribute("active", active.to_string()))
+}
+
+/// Freeze a deactivated agent's karma, or thaw it once the agent is active
+/// again, and return whether the agent is active
+///
+/// Freezing settles decay up to now and takes the agent off the leaderboards.
+/// Thawing restarts decay from now, and moves the latest rating time that
+/// recalculations decay from forward by the time spent frozen, so that time
+/// never counts.
+fn sync_agent_status(mut deps: DepsMut, env: &Env, agent: &Addr) -> Result<bool, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let agent_str = agent.as_str();
+    let active = query_is_registered(deps.as_ref(), &config.agent_registry, agent_str)?;
+    let frozen = FROZEN_AGENTS.has(deps.storage, agent_str);
+    if active != frozen {
+        return Ok(active);
+    }
+
+    let half_life = config.karma_config.decay_half_life;
+    let settle = |karma: &mut KarmaScore, adjustment: Int128| {
+        if !active {
+            karma.current_score = project_karma_score(karma, adjustment, half_life, env.block.time);
+        }
+        karma.last_updated = env.block.time;
+    };
+
+    let mut score = Uint128::zero();
+    if let Some(mut karma) = KARMA_SCORES.may_load(deps.storage, agent_str)? {
+        let before = karma.current_score;
+        let adjustment = KARMA_ADJUSTMENTS.may_load(deps.storage, agent_str)?.unwrap_or_default();
+        settle(&mut karma, adjustment);
+        KARMA_SCORES.save(deps.storage, agent_str, &karma)?;
+        score = karma.current_score;
+        let posting = Posting::new(KarmaLedgerReason::Decay, None, None);
+        record_karma_movement(deps.storage, env, agent_str, before, score, posting)?;
+    }
+
+    let categories = CATEGORY_KARMA
+        .prefix(agent_str)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (interaction_type, mut karma) in categories {
+        settle(&mut karma, Int128::zero());
+        CATEGORY_KARMA.save(deps.storage, (agent_str, interaction_type.as_str()), &karma)?;
+        let listed = if active { karma.current_score } else { Uint128::zero() };
+        update_category_leaderboard(deps.storage, &interaction_type, agent_str, listed)?;
+    }
+
+    if active {
+        let frozen_at = FROZEN_AGENTS.load(deps.storage, agent_str)?;
+        defer_decay(deps.storage, agent_str, env.block.time.seconds().saturating_sub(frozen_at.seconds()))?;
+        FROZEN_AGENTS.remove(deps.storage, agent_str);
+        update_leaderboard(deps.branch(), agent, score)?;
+    } else {
+        FROZEN_AGENTS.save(deps.storage, agent_str, &env.block.time)?;
+        update_leaderboard(deps.branch(), agent, Uint128::zero())?;
+    }
+
+    Ok(active)
+}
+
 pub fn execute_update_config(
     deps: DepsMut,
     _env: Env,
@@ -308,6 +1016,25 @@ pub fn execute_update_config(
         });
     }
 
+    if new_config.decay_half_life == 0 {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Decay half-life cannot be zero".to_string(),
+        });
+    }
+
+    if new_config.feedback_flag_threshold == 0 {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Feedback flag threshold cannot be zero".to_string(),
+        });
+    }
+
+    validate_algorithm_config(&new_config.algorithm)?;
+    validate_trust_config(deps.api, &new_config.trust)?;
+    validate_arbitration_config(&new_config.arbitration)?;
+    validate_rate_limit_config(&new_config.rate_limits)?;
+    validate_enforcement_config(&new_config.enforcement)?;
+    validate_rehabilitation_config(&new_config.rehabilitation)?;
+
     config.karma_config = new_config.clone();
     CONFIG.save(deps.storage, &config)?;
 
@@ -316,7 +1043,8 @@ pub fn execute_update_config(
         .add_attribute("admin", info.sender)
         .add_attribute("min_karma_for_rating", new_config.min_karma_for_rating)
         .add_attribute("rating_window", new_config.rating_window.to_string())
-        .add_attribute("rating_fee", new_config.rating_fee))
+        .add_attribute("rating_fee", new_config.rating_fee)
+        .add_attribute("algorithm_version", format!("{:?}", new_config.algorithm.version)))
 }
 
 pub fn execute_process_oracle_data(
@@ -334,6 +1062,9 @@ pub fn execute_process_oracle_data(
     }
 
     let agent_addr = deps.api.addr_validate(&agent_address)?;
+    if !sync_agent_status(deps.branch(), &env, &agent_addr)? {
+        return Err(ContractError::AgentNotFound { address: agent_address });
+    }
 
     // Process each oracle data entry
     for data in oracle_data.iter() {
@@ -342,24 +1073,22 @@ pub fn execute_process_oracle_data(
             return Err(ContractError::OracleDataVerificationFailed {});
         }
 
-        // Store oracle data hash for karma calculation
-        let data_key = (agent_address.as_str(), data.data_type.as_str());
-        let data_hash = format!("{}:{}", data.timestamp.seconds(), data.data.len());
-        ORACLE_DATA.save(deps.storage, data_key, &data_hash)?;
+        // Store the parsed payload for karma calculation
+        record_oracle_data(deps.storage, agent_addr.as_str(), data, env.block.time)?;
     }
 
     // Recalculate karma with new oracle data
     let karma_calculation = calculate_karma_score(deps.as_ref(), &env, &agent_addr)?;
-    update_karma_score(deps.branch(), &env, &agent_addr, &karma_calculation)?;
+    let new_karma = update_karma_score(deps.branch(), &env, &agent_addr, &karma_calculation)?;
 
     // Update leaderboard
-    update_leaderboard(deps.branch(), &agent_addr, karma_calculation.current_score)?;
+    update_leaderboard(deps.branch(), &agent_addr, new_karma)?;
 
     Ok(Response::new()
         .add_attribute("method", "process_oracle_data")
         .add_attribute("agent_address", agent_address)
         .add_attribute("oracle_entries", oracle_data.len().to_string())
-        .add_attribute("new_karma", karma_calculation.current_score))
+        .add_attribute("new_karma", new_karma))
 }
 
 #[entry_point]
@@ -368,38 +1097,118 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetKarmaScore { agent_address } => {
             to_json_binary(&query_get_karma_score(deps, env, agent_address)?)
         }
+        QueryMsg::GetKarmaScoreAt { agent_address, time } => {
+            to_json_binary(&query_get_karma_score_at(deps, agent_address, time)?)
+        }
         QueryMsg::GetKarmaCalculation { agent_address } => {
             to_json_binary(&query_get_karma_calculation(deps, env, agent_address)?)
         }
+        QueryMsg::GetKarmaBreakdown { agent_address } => {
+            to_json_binary(&query_get_karma_breakdown(deps, env, agent_address)?)
+        }
         QueryMsg::GetKarmaHistory {
             agent_address,
+            from,
+            to,
             start_after,
             limit,
         } => to_json_binary(&query_get_karma_history(
             deps,
-            env,
             agent_address,
+            from,
+            to,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetKarmaHistoryBuckets {
+            agent_address,
+            interval,
+            from,
+            to,
+            start_after,
+            limit,
+        } => to_json_binary(&query_get_karma_history_buckets(
+            deps,
+            agent_address,
+            interval,
+            from,
+            to,
             start_after,
             limit,
         )?),
+        QueryMsg::GetKarmaLedger {
+            agent_address,
+            start_after,
+            limit,
+        } => to_json_binary(&query_get_karma_ledger(deps, agent_address, start_after, limit)?),
+        QueryMsg::GetRatingRevisions { rating_id } => {
+            to_json_binary(&query_get_rating_revisions(deps, rating_id)?)
+        }
         QueryMsg::GetAgentRatings {
             agent_address,
             start_after,
             limit,
+            include_hidden_feedback,
         } => to_json_binary(&query_get_agent_ratings(
             deps,
             agent_address,
             start_after,
             limit,
+            include_hidden_feedback,
+        )?),
+        QueryMsg::GetLeaderboard { start_after, limit } => {
+            to_json_binary(&query_get_leaderboard(deps, start_after, limit)?)
+        }
+        QueryMsg::GetLeaderboardByFramework {
+            framework,
+            start_after,
+            limit,
+        } => to_json_binary(&query_get_leaderboard_by_framework(
+            deps,
+            framework,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetCategoryKarma { agent_address } => {
+            to_json_binary(&query_get_category_karma(deps, env, agent_address)?)
+        }
+        QueryMsg::GetCategoryLeaderboard {
+            interaction_type,
+            start_after,
+            limit,
+        } => to_json_binary(&query_get_category_leaderboard(
+            deps,
+            interaction_type,
+            start_after,
+            limit,
         )?),
-        QueryMsg::GetLeaderboard { limit } => to_json_binary(&query_get_leaderboard(deps, limit)?),
+        QueryMsg::GetAgentRank { agent_address } => {
+            to_json_binary(&query_get_agent_rank(deps, agent_address)?)
+        }
+        QueryMsg::GetTrustScore { agent_address } => {
+            to_json_binary(&query_get_trust_score(deps, agent_address)?)
+        }
         QueryMsg::GetConfig {} => to_json_binary(&query_get_config(deps)?),
+        QueryMsg::SimulateRating {
+            rater,
+            rated_agent,
+            score,
+            interaction_hash,
+        } => to_json_binary(&query_simulate_rating(
+            deps,
+            env,
+            rater,
+            rated_agent,
+            score,
+            interaction_hash,
+        )?),
         QueryMsg::GetComplianceViolations {
             agent_address,
             start_after,
             limit,
         } => to_json_binary(&query_get_compliance_violations(
             deps,
+            env,
             agent_address,
             start_after,
             limit,
@@ -409,8 +1218,12 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after,
             limit,
         } => to_json_binary(&query_get_dispute_cases(deps, status, start_after, limit)?),
+        QueryMsg::GetArbitration { case_id } => to_json_binary(&query_get_arbitration(deps, case_id)?),
         QueryMsg::GetAbuseDetectionResults { agent_address } => {
-            to_json_binary(&query_get_abuse_detection_results(deps, agent_address)?)
+            to_json_binary(&query_get_abuse_detection_results(deps, env, agent_address)?)
+        }
+        QueryMsg::GetComplianceReport { agent_address } => {
+            to_json_binary(&query_get_compliance_report(deps, env, agent_address)?)
         }
         QueryMsg::GetRateLimitStatus {
             agent_address,
@@ -424,13 +1237,30 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     }
 }
 
-pub fn query_get_karma_score(deps: Deps, _env: Env, agent_address: String) -> StdResult<KarmaScoreResponse> {
+pub fn query_get_karma_score(deps: Deps, env: Env, agent_address: String) -> StdResult<KarmaScoreResponse> {
+    query_get_karma_score_at(deps, agent_address, env.block.time)
+}
+
+pub fn query_get_karma_score_at(
+    deps: Deps,
+    agent_address: String,
+    time: cosmwasm_std::Timestamp,
+) -> StdResult<KarmaScoreResponse> {
+    let config = CONFIG.load(deps.storage)?;
     let karma = KARMA_SCORES
         .may_load(deps.storage, &agent_address)?
         .unwrap_or_default();
 
+    // Decay is applied lazily so the stored score never goes stale; frozen
+    // scores do not decay
+    let score = if FROZEN_AGENTS.has(deps.storage, &agent_address) {
+        karma.current_score
+    } else {
+        let adjustment = KARMA_ADJUSTMENTS.may_load(deps.storage, &agent_address)?.unwrap_or_default();
+        project_karma_score(&karma, adjustment, config.karma_config.decay_half_life, time)
+    };
     Ok(KarmaScoreResponse {
-        score: karma.current_score,
+        score,
         last_updated: karma.last_updated,
     })
 }
@@ -443,14 +1273,8 @@ pub fn query_get_karma_calculation(
     let agent_addr = deps.api.addr_validate(&agent_address)?;
 
     // Get the most recent calculation from history
-    let history: StdResult<Vec<_>> = KARMA_HISTORY
-        .prefix(&agent_address)
-        .range(deps.storage, None, None, Order::Descending)
-        .take(1)
-        .collect();
-
-    let calculation = match history?.first() {
-        Some((_, calc)) => calc.clone(),
+    let calculation = match latest_history(deps.storage, &agent_address)? {
+        Some(calc) => calc,
         None => {
             // Return default calculation if no history exists
             agent_karma_contracts::types::KarmaCalculation {
@@ -458,87 +1282,379 @@ pub fn query_get_karma_calculation(
                 current_score: Uint128::zero(),
                 previous_score: Uint128::zero(),
                 factors: agent_karma_contracts::types::KarmaFactors {
-                    average_rating: "0.0".to_string(),
+                    average_rating: cosmwasm_std::Decimal::zero(),
                     rating_count: 0,
                     interaction_frequency: Uint128::zero(),
-                    time_decay: "1.0".to_string(),
+                    time_decay: cosmwasm_std::Decimal::one(),
                     external_factors: Some(Uint128::zero()),
                 },
                 last_updated: cosmwasm_std::Timestamp::from_seconds(0),
                 calculation_hash: "".to_string(),
+                algorithm_version: CONFIG.load(deps.storage)?.karma_config.algorithm.version,
             }
         }
     };
 
-    Ok(KarmaCalculationResponse { calculation })
+    Ok(KarmaCalculationResponse { calculation })
+}
+
+pub fn query_get_category_karma(
+    deps: Deps,
+    env: Env,
+    agent_address: String,
+) -> StdResult<CategoryKarmaResponse> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    // Scores decay lazily, like the global score
+    let frozen = FROZEN_AGENTS.has(deps.storage, &agent_address);
+    let categories: StdResult<Vec<_>> = CATEGORY_KARMA
+        .prefix(agent_address.as_str())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (interaction_type, karma) = item?;
+            Ok(CategoryKarma {
+                interaction_type,
+                score: if frozen {
+                    karma.current_score
+                } else {
+                    project_karma_score(&karma, Int128::zero(), config.karma_config.decay_half_life, env.block.time)
+                },
+                rating_count: karma.total_ratings,
+                last_updated: karma.last_updated,
+            })
+        })
+        .collect();
+
+    Ok(CategoryKarmaResponse {
+        agent_address: agent_addr,
+        categories: categories?,
+    })
+}
+
+pub fn query_get_karma_breakdown(
+    deps: Deps,
+    env: Env,
+    agent_address: String,
+) -> StdResult<KarmaBreakdownResponse> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+
+    // Explain the score as it would be recalculated at the current block
+    let computation = compute_karma(deps, &env, &agent_addr)
+        .map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    Ok(KarmaBreakdownResponse {
+        agent_address: agent_addr,
+        score: computation.calculation.current_score,
+        algorithm_version: computation.calculation.algorithm_version,
+        calculated_at: computation.calculation.last_updated,
+        components: computation.components,
+    })
+}
+
+pub fn query_get_karma_history(
+    deps: Deps,
+    agent_address: String,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    start_after: Option<KarmaHistoryCursor>,
+    limit: Option<u32>,
+) -> StdResult<KarmaHistoryResponse> {
+    let limit = limit.unwrap_or(50).min(100) as usize;
+
+    let (history, next_cursor) =
+        history_page(deps.storage, &agent_address, from, to, start_after, limit)?;
+
+    Ok(KarmaHistoryResponse { history, next_cursor })
+}
+
+pub fn query_get_karma_ledger(
+    deps: Deps,
+    agent_address: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<KarmaLedgerResponse> {
+    let limit = limit.unwrap_or(50).min(100) as usize;
+
+    let (entries, totals) = ledger_page(deps.storage, &agent_address, start_after, limit)?;
+
+    Ok(KarmaLedgerResponse {
+        entries,
+        entry_count: totals.entries,
+        total_credited: totals.credited,
+        total_debited: totals.debited,
+    })
+}
+
+pub fn query_get_karma_history_buckets(
+    deps: Deps,
+    agent_address: String,
+    interval: HistoryInterval,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    start_after: Option<Timestamp>,
+    limit: Option<u32>,
+) -> StdResult<KarmaHistoryBucketsResponse> {
+    let limit = limit.unwrap_or(90).min(366) as usize;
+
+    Ok(KarmaHistoryBucketsResponse {
+        buckets: history_buckets(deps.storage, &agent_address, interval, from, to, start_after, limit)?,
+    })
+}
+
+pub fn query_get_agent_ratings(
+    deps: Deps,
+    agent_address: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    include_hidden_feedback: Option<bool>,
+) -> StdResult<RatingsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(50).min(100) as usize;
+    let include_hidden_feedback = include_hidden_feedback.unwrap_or(false);
+
+    let start_bound = start_after.as_ref().map(|id| Bound::exclusive(id.as_str()));
+
+    let stored_ratings = ratings()
+        .idx
+        .rated_agent
+        .prefix(agent_address)
+        .range(deps.storage, start_bound, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, stored_rating)| stored_rating))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut response = RatingsResponse {
+        ratings: vec![],
+        replies: vec![],
+        hidden_feedback: vec![],
+    };
+    for stored in stored_ratings {
+        let hidden = feedback_hidden(&stored, config.karma_config.feedback_flag_threshold);
+        let mut rating = stored.rating;
+        if hidden {
+            if !include_hidden_feedback {
+                rating.feedback = None;
+            }
+            response.hidden_feedback.push(rating.id.clone());
+        }
+        response.replies.extend(stored.reply);
+        response.ratings.push(rating);
+    }
+
+    Ok(response)
+}
+
+pub fn query_get_rating_revisions(deps: Deps, rating_id: String) -> StdResult<RatingRevisionsResponse> {
+    let stored = match ratings().may_load(deps.storage, &rating_id)? {
+        Some(stored) => stored,
+        None => RETRACTED_RATINGS.load(deps.storage, &rating_id)?,
+    };
+
+    Ok(RatingRevisionsResponse {
+        rating: stored.rating,
+        revisions: stored.revisions,
+        retracted_at: stored.retracted_at,
+    })
+}
+
+pub fn query_get_leaderboard(
+    deps: Deps,
+    start_after: Option<LeaderboardCursor>,
+    limit: Option<u32>,
+) -> StdResult<LeaderboardResponse> {
+    leaderboard_page(deps, None, None, start_after, limit)
+}
+
+pub fn query_get_leaderboard_by_framework(
+    deps: Deps,
+    framework: String,
+    start_after: Option<LeaderboardCursor>,
+    limit: Option<u32>,
+) -> StdResult<LeaderboardResponse> {
+    leaderboard_page(deps, None, Some(&framework), start_after, limit)
+}
+
+pub fn query_get_category_leaderboard(
+    deps: Deps,
+    interaction_type: String,
+    start_after: Option<LeaderboardCursor>,
+    limit: Option<u32>,
+) -> StdResult<LeaderboardResponse> {
+    leaderboard_page(deps, Some(&interaction_type), None, start_after, limit)
+}
+
+/// Walk a leaderboard index in rank order, enriching each entry from the
+/// agent registry until `limit` entries are collected
+///
+/// Lists the global leaderboard, or the one of `interaction_type` when set.
+/// Agents the registry no longer lists as active are skipped, as are agents
+/// of other frameworks when `framework` is set. Pages resume after the
+/// (score, agent) position of the previous page, so agents moving between
+/// pages never restart the listing.
+///
+/// At most `MAX_LEADERBOARD_SCAN` entries are looked up per page; a page cut
+/// short by the cap still returns a cursor to continue from.
+///
+/// Entries carry the score the agent is listed under, its karma as of the
+/// last calculation. `GetKarmaScore` decays karma up to query time, so an
+/// agent inactive for longer can rank above one whose queried karma is higher.
+fn leaderboard_page(
+    deps: Deps,
+    interaction_type: Option<&str>,
+    framework: Option<&str>,
+    start_after: Option<LeaderboardCursor>,
+    limit: Option<u32>,
+) -> StdResult<LeaderboardResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(20).min(100) as usize;
+
+    let start = start_after
+        .as_ref()
+        .map(|cursor| Bound::exclusive((leaderboard_key(cursor.karma_score.u128()), cursor.agent_address.as_str())));
+    let mut keys: Box<dyn Iterator<Item = StdResult<(u128, String)>>> = match interaction_type {
+        Some(interaction_type) => CATEGORY_LEADERBOARD
+            .sub_prefix(interaction_type)
+            .keys(deps.storage, start, None, Order::Ascending),
+        None => LEADERBOARD.keys(deps.storage, start, None, Order::Ascending),
+    };
+
+    let mut entries = Vec::with_capacity(limit);
+    let mut next_cursor = None;
+    let mut scanned = 0;
+    while let Some(item) = keys.next() {
+        let (key, agent_address) = item?;
+        let cursor = LeaderboardCursor {
+            karma_score: Uint128::from(u128::MAX - key),
+            agent_address,
+        };
+        scanned += 1;
+
+        let listed = query_registered_agent(deps, &config.agent_registry, &cursor.agent_address)?
+            .filter(|agent| framework.is_none_or(|framework| agent.metadata.framework == framework));
+        if let Some(agent) = listed {
+            entries.push(LeaderboardEntry {
+                agent_address: Addr::unchecked(&cursor.agent_address),
+                karma_score: cursor.karma_score,
+                agent_name: agent.metadata.name,
+                framework: agent.metadata.framework,
+            });
+        }
+
+        if entries.len() == limit || scanned == MAX_LEADERBOARD_SCAN {
+            if keys.next().is_some() {
+                next_cursor = Some(cursor);
+            }
+            break;
+        }
+    }
+
+    Ok(LeaderboardResponse {
+        leaderboard: entries,
+        next_cursor,
+    })
 }
 
-pub fn query_get_karma_history(
-    deps: Deps,
-    _env: Env,
-    agent_address: String,
-    start_after: Option<cosmwasm_std::Timestamp>,
-    limit: Option<u32>,
-) -> StdResult<KarmaHistoryResponse> {
-    let limit = limit.unwrap_or(50).min(100) as usize;
-
-    let start_bound = start_after.map(|ts| Bound::exclusive(ts.seconds()));
+/// Rank an agent on the leaderboard
+///
+/// Rank is one more than the number of entries listed ahead of the agent.
+/// At most `MAX_RANK_SCAN` of them are counted, so agents listed further down
+/// are only reported as ranked below that position.
+pub fn query_get_agent_rank(deps: Deps, agent_address: String) -> StdResult<AgentRankResponse> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+    let total_ranked = LEADERBOARD_SIZE.may_load(deps.storage)?.unwrap_or(0);
 
-    let history: StdResult<Vec<_>> = KARMA_HISTORY
-        .prefix(&agent_address)
-        .range(deps.storage, start_bound, None, Order::Descending)
-        .take(limit)
-        .map(|item| item.map(|(_, calc)| calc))
-        .collect();
+    let mut response = AgentRankResponse {
+        agent_address: agent_addr,
+        rank: None,
+        ranked_below: None,
+        karma_score: Uint128::zero(),
+        total_ranked,
+    };
+    if let Some(score) = LEADERBOARD_SCORES.may_load(deps.storage, &agent_address)? {
+        let ahead = LEADERBOARD
+            .keys_raw(
+                deps.storage,
+                None,
+                Some(Bound::exclusive((leaderboard_key(score), agent_address.as_str()))),
+                Order::Ascending,
+            )
+            .take(MAX_RANK_SCAN)
+            .count();
+        if ahead < MAX_RANK_SCAN {
+            response.rank = Some(ahead as u64 + 1);
+        } else {
+            response.ranked_below = Some(MAX_RANK_SCAN as u64);
+        }
+        response.karma_score = Uint128::from(score);
+    }
 
-    Ok(KarmaHistoryResponse { history: history? })
+    Ok(response)
 }
 
-pub fn query_get_agent_ratings(
+pub fn query_simulate_rating(
     deps: Deps,
-    agent_address: String,
-    start_after: Option<String>,
-    limit: Option<u32>,
-) -> StdResult<RatingsResponse> {
-    let limit = limit.unwrap_or(50).min(100) as usize;
-
-    let start_bound = start_after.as_ref().map(|id| Bound::exclusive(id.as_str()));
+    env: Env,
+    rater: String,
+    rated_agent: String,
+    score: u8,
+    interaction_hash: String,
+) -> StdResult<SimulateRatingResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let rater_addr = deps.api.addr_validate(&rater)?;
+    let current_score = KARMA_SCORES
+        .may_load(deps.storage, &rated_agent)?
+        .map(|karma| karma.current_score)
+        .unwrap_or_default();
 
-    let ratings_result: StdResult<Vec<_>> = ratings()
-        .idx
-        .rated_agent
-        .prefix(agent_address)
-        .range(deps.storage, start_bound, None, Order::Ascending)
-        .take(limit)
-        .map(|item| item.map(|(_, stored_rating)| stored_rating.rating))
-        .collect();
+    // Run the real submission against a throwaway copy-on-write view of storage
+    let mut storage = OverlayStorage::new(deps.storage);
+    let sandbox = DepsMut {
+        storage: &mut storage,
+        api: deps.api,
+        querier: deps.querier,
+    };
+    let outcome = submit_rating(
+        sandbox,
+        &env,
+        &rater_addr,
+        &rated_agent,
+        score,
+        None,
+        &interaction_hash,
+    );
 
-    Ok(RatingsResponse {
-        ratings: ratings_result?,
+    Ok(match outcome {
+        Ok(outcome) => SimulateRatingResponse {
+            success: true,
+            error: None,
+            current_score,
+            projected_score: Some(outcome.new_karma),
+            fee: outcome.fee_paid,
+            karma_earned: outcome.karma_earned,
+            karma_penalty: outcome.karma_penalty,
+        },
+        Err(err) => SimulateRatingResponse {
+            success: false,
+            error: Some(err.to_string()),
+            current_score,
+            projected_score: None,
+            fee: config.karma_config.rating_fee,
+            karma_earned: Uint128::zero(),
+            karma_penalty: Uint128::zero(),
+        },
     })
 }
 
-pub fn query_get_leaderboard(deps: Deps, limit: Option<u32>) -> StdResult<LeaderboardResponse> {
-    let limit = limit.unwrap_or(20).min(100) as usize;
-
-    let leaderboard_entries: StdResult<Vec<_>> = LEADERBOARD
-        .range(deps.storage, None, None, Order::Descending)
-        .take(limit)
-        .map(|item| {
-            let (karma_score, agent_address) = item?;
-            let agent_karma_score = KARMA_SCORES.load(deps.storage, &agent_address)?;
-
-            Ok(LeaderboardEntry {
-                agent_address: Addr::unchecked(&agent_address),
-                karma_score: Uint128::from(karma_score),
-                agent_name: "Unknown".to_string(), // Would query agent registry in real implementation
-                framework: "Unknown".to_string(), // Would query agent registry in real implementation
-            })
-        })
-        .collect();
+pub fn query_get_trust_score(deps: Deps, agent_address: String) -> StdResult<TrustScoreResponse> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+    let config = CONFIG.load(deps.storage)?;
 
-    Ok(LeaderboardResponse {
-        leaderboard: leaderboard_entries?,
+    Ok(TrustScoreResponse {
+        trust: query_trust(deps.storage, &config.karma_config.trust, &agent_address)?,
+        credibility: TRUST_CREDIBILITY.may_load(deps.storage, &agent_address)?,
+        epoch: TRUST_EPOCH.may_load(deps.storage)?.unwrap_or_default().epoch,
+        agent_address: agent_addr,
     })
 }
 
@@ -553,37 +1669,39 @@ pub fn query_get_config(deps: Deps) -> StdResult<ConfigResponse> {
 
 pub fn query_get_compliance_violations(
     deps: Deps,
+    env: Env,
     agent_address: String,
     start_after: Option<String>,
     limit: Option<u32>,
 ) -> StdResult<ComplianceViolationsResponse> {
+    let config = CONFIG.load(deps.storage)?;
     let limit = limit.unwrap_or(50).min(100) as usize;
 
     let start_bound = start_after.as_ref().map(|id| Bound::exclusive(id.as_str()));
 
-    let violations: StdResult<Vec<_>> = crate::state::COMPLIANCE_VIOLATIONS
-        .range(deps.storage, start_bound, None, Order::Ascending)
-        .filter_map(|item| match item {
-            Ok((_violation_id, violation)) => {
-                if violation.agent_address.to_string() == agent_address {
-                    Some(Ok(
-                        agent_karma_contracts::messages::karma_core::ComplianceViolation {
-                            agent_address: violation.agent_address,
-                            violation_type: format!("{:?}", violation.violation_type),
-                            severity: violation.severity,
-                            timestamp: violation.timestamp,
-                            evidence: violation.evidence,
-                            penalty_applied: violation.penalty_applied,
-                            disputed: violation.disputed,
-                        },
-                    ))
-                } else {
-                    None
-                }
-            }
-            Err(e) => Some(Err(e)),
-        })
+    let violations: StdResult<Vec<_>> = crate::state::AGENT_VIOLATIONS
+        .prefix(&agent_address)
+        .keys(deps.storage, start_bound, None, Order::Ascending)
         .take(limit)
+        .map(|violation_id| {
+            let violation = COMPLIANCE_VIOLATIONS.load(deps.storage, &violation_id?)?;
+            let rehabilitation = &config.karma_config.rehabilitation;
+            Ok(agent_karma_contracts::messages::karma_core::ComplianceViolation {
+                expires_at: violation_expires_at(rehabilitation, &violation),
+                active: is_violation_active(rehabilitation, &violation, env.block.time),
+                agent_address: violation.agent_address,
+                violation_type: format!("{:?}", violation.violation_type),
+                severity: violation.severity,
+                timestamp: violation.timestamp,
+                evidence: violation.evidence,
+                penalty_applied: violation.penalty_applied,
+                disputed: violation.disputed,
+                penalty_refunded: violation.penalty_refunded,
+                overturned: violation.overturned,
+                enforcement: violation.enforcement,
+                penalty_recovered: violation.penalty_recovered,
+            })
+        })
         .collect();
 
     Ok(ComplianceViolationsResponse {
@@ -618,6 +1736,7 @@ pub fn query_get_dispute_cases(
                             created_at: case.created_at,
                             resolved_at: case.resolved_at,
                             resolution: case.resolution.map(|r| format!("{:?}", r)),
+                            juror_draw_height: case.juror_draw_height,
                         },
                     ))
                 } else {
@@ -632,116 +1751,238 @@ pub fn query_get_dispute_cases(
     Ok(DisputeCasesResponse { cases: cases? })
 }
 
+pub fn query_get_arbitration(deps: Deps, case_id: String) -> StdResult<ArbitrationResponse> {
+    let arbitration = ARBITRATIONS.load(deps.storage, &case_id)?;
+
+    let mut jurors = vec![];
+    for juror in arbitration.jurors {
+        let vote = JUROR_VOTES.may_load(deps.storage, (&case_id, juror.as_str()))?;
+        jurors.push(JurorStatus {
+            committed: vote.is_some(),
+            vote: vote.and_then(|vote| vote.revealed).map(|r| format!("{:?}", r)),
+            juror,
+        });
+    }
+
+    Ok(ArbitrationResponse {
+        case_id,
+        jurors,
+        commit_deadline: arbitration.commit_deadline,
+        reveal_deadline: arbitration.reveal_deadline,
+        resolution: arbitration.resolution.map(|r| format!("{:?}", r)),
+    })
+}
+
 pub fn query_get_abuse_detection_results(
     deps: Deps,
+    env: Env,
     agent_address: String,
 ) -> StdResult<AbuseDetectionResponse> {
     let agent_addr = deps.api.addr_validate(&agent_address)?;
 
-    // Create a mock environment for detection (in real implementation, would use current env)
-    let mock_env = cosmwasm_std::Env {
-        block: cosmwasm_std::BlockInfo {
-            height: 0,
-            time: cosmwasm_std::Timestamp::from_seconds(0),
-            chain_id: "test".to_string(),
-        },
-        transaction: None,
-        contract: cosmwasm_std::ContractInfo {
-            address: cosmwasm_std::Addr::unchecked("contract"),
-        },
-    };
-
-    // Run abuse detection
-    let detection_results =
-        crate::compliance::run_abuse_detection(deps, &mock_env, &agent_addr).unwrap_or_default();
+    // Detection windows end at the queried block
+    let detection_results = crate::compliance::run_abuse_detection(deps, &env, &agent_addr)?;
 
     let results: Vec<_> = detection_results
         .into_iter()
-        .map(|result| AbuseDetectionResult {
-            is_suspicious: result.is_suspicious,
-            violation_type: result.violation_type.map(|v| format!("{:?}", v)),
-            confidence_score: format!("{:.2}", result.confidence_score),
-            evidence: result.evidence,
-            recommended_penalty: result.recommended_penalty,
-        })
+        .map(detection_result_response)
         .collect();
 
     Ok(AbuseDetectionResponse { results })
 }
 
+fn detection_result_response(result: crate::compliance::AbuseDetectionResult) -> AbuseDetectionResult {
+    AbuseDetectionResult {
+        is_suspicious: result.is_suspicious,
+        violation_type: result.violation_type.map(|v| format!("{:?}", v)),
+        confidence_score: format!("{:.2}", result.confidence_score),
+        evidence: result.evidence,
+        recommended_penalty: result.recommended_penalty,
+    }
+}
+
 pub fn query_get_rate_limit_status(
     deps: Deps,
-    _env: Env,
+    env: Env,
     agent_address: String,
     action_type: String,
 ) -> StdResult<RateLimitStatusResponse> {
     let agent_addr = deps.api.addr_validate(&agent_address)?;
 
-    // Get rate limit tracker
-    let tracker =
-        crate::state::RATE_LIMIT_TRACKERS.may_load(deps.storage, agent_address.as_str())?;
+    let config = rate_limit_config(deps.storage)?;
+    let status = rate_limit_status(deps.storage, &env, &config, &agent_addr, action_type)?;
 
-    let current_time = _env.block.time;
-    let window_duration = crate::compliance::RATING_PATTERN_WINDOW;
+    Ok(RateLimitStatusResponse { status })
+}
 
-    let (current_count, limit, window_start, remaining_actions) = if let Some(tracker) = tracker {
-        // Check if window has expired
-        let window_expired =
-            current_time.seconds() - tracker.window_start.seconds() > window_duration;
-        let count = if window_expired { 0 } else { tracker.count };
+fn rate_limit_status(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &RateLimitConfig,
+    agent: &Addr,
+    action_type: String,
+) -> StdResult<RateLimitStatus> {
+    let quota = quota(storage, config, agent.as_str(), &action_type, env.block.time)?;
 
-        // Get karma-based limit
-        let karma_score = crate::state::KARMA_SCORES
-            .may_load(deps.storage, &agent_address)?
-            .unwrap_or_default();
+    Ok(RateLimitStatus {
+        agent_address: agent.clone(),
+        action_type,
+        current_count: quota.used,
+        limit: quota.limit,
+        window_start: env.block.time.minus_seconds(quota.window),
+        window_end: env.block.time,
+        remaining_actions: quota.remaining(),
+        resets_at: quota.resets_at,
+    })
+}
 
-        let base_limit = match action_type.as_str() {
-            "rating" => crate::compliance::SPAM_RATING_THRESHOLD,
-            "interaction" => crate::compliance::BOT_BEHAVIOR_THRESHOLD,
-            _ => 20,
-        };
+pub fn query_get_compliance_report(
+    deps: Deps,
+    env: Env,
+    agent_address: String,
+) -> StdResult<ComplianceReportResponse> {
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+    let config = CONFIG.load(deps.storage)?;
+    let rehabilitation = &config.karma_config.rehabilitation;
+
+    // Violations by type, and the penalties they carried
+    let violations = agent_violations(deps.storage, agent_addr.as_str())?;
+    let mut counts: BTreeMap<&str, ViolationCount> = BTreeMap::new();
+    let (mut penalty_applied, mut penalty_refunded, mut penalty_recovered, mut outstanding_penalty) =
+        (Uint128::zero(), Uint128::zero(), Uint128::zero(), Uint128::zero());
+    let (mut active_severity, mut escalated) = (0u32, false);
+    for (_, violation) in violations.iter() {
+        let violation_type = violation.violation_type.as_str();
+        let count = counts.entry(violation_type).or_insert_with(|| ViolationCount {
+            violation_type: violation_type.to_string(),
+            total: 0,
+            active: 0,
+        });
+        count.total += 1;
+        if is_violation_active(rehabilitation, violation, env.block.time) {
+            count.active += 1;
+            active_severity += violation.severity as u32;
+            escalated |= violation.enforcement == Some(EnforcementAction::Escalate);
+        }
+        penalty_applied += violation.penalty_applied;
+        penalty_refunded += violation.penalty_refunded;
+        penalty_recovered += violation.penalty_recovered;
+        outstanding_penalty += violation
+            .penalty_applied
+            .saturating_sub(violation.penalty_refunded)
+            .saturating_sub(violation.penalty_recovered);
+    }
 
-        let karma_multiplier = if karma_score.current_score.u128() > 1000 {
-            2.0
-        } else if karma_score.current_score.u128() > 500 {
-            1.5
-        } else if karma_score.current_score.u128() > 100 {
-            1.2
-        } else {
-            1.0
-        };
+    let (mut open_disputes, mut resolved_disputes) = (0, 0);
+    for (violation_id, _) in violations.iter() {
+        for case_id in VIOLATION_DISPUTES
+            .prefix(violation_id.as_str())
+            .keys(deps.storage, None, None, Order::Ascending)
+        {
+            let dispute_case = DISPUTE_CASES.load(deps.storage, &case_id?)?;
+            match dispute_case.status {
+                DisputeStatus::Pending | DisputeStatus::UnderReview => open_disputes += 1,
+                DisputeStatus::Resolved | DisputeStatus::Rejected => resolved_disputes += 1,
+            }
+        }
+    }
 
-        let effective_limit = (base_limit as f64 * karma_multiplier) as u32;
-        let remaining = effective_limit.saturating_sub(count);
+    let rate_limit_config = &config.karma_config.rate_limits;
+    let rate_limits = rate_limit_config
+        .actions
+        .iter()
+        .map(|limit| limit.action.clone())
+        .chain(std::iter::once(EARNING_ACTION.to_string()))
+        .map(|action| rate_limit_status(deps.storage, &env, rate_limit_config, &agent_addr, action))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let rating_suspended_until = rating_suspension(deps.storage, agent_addr.as_str(), env.block.time)?;
+
+    let mut latest_detections = vec![];
+    for detector in DETECTORS.iter() {
+        if let Some(run) = ABUSE_PATTERNS.may_load(deps.storage, (agent_addr.as_str(), detector.as_str()))? {
+            latest_detections.push(DetectorReport {
+                detector: detector.as_str().to_string(),
+                detected_at: run.detected_at,
+                result: run.result,
+            });
+        }
+    }
 
-        (count, effective_limit, tracker.window_start, remaining)
+    let risk_tier = if rating_suspended_until.is_some() || escalated || active_severity >= 20 {
+        ComplianceRiskTier::Critical
+    } else if active_severity >= 10 || latest_detections.iter().any(|run| run.result.is_suspicious) {
+        ComplianceRiskTier::High
+    } else if counts.values().any(|count| count.active > 0) {
+        ComplianceRiskTier::Elevated
     } else {
-        // No tracker exists, use defaults
-        let base_limit = match action_type.as_str() {
-            "rating" => crate::compliance::SPAM_RATING_THRESHOLD,
-            "interaction" => crate::compliance::BOT_BEHAVIOR_THRESHOLD,
-            _ => 20,
-        };
-        (0, base_limit, current_time, base_limit)
+        ComplianceRiskTier::Low
     };
 
-    let status = RateLimitStatus {
+    Ok(ComplianceReportResponse {
         agent_address: agent_addr,
-        action_type,
-        current_count,
-        limit,
-        window_start,
-        window_end: window_start.plus_seconds(window_duration),
-        remaining_actions,
-    };
-
-    Ok(RateLimitStatusResponse { status })
+        risk_tier,
+        violations: counts.into_values().collect(),
+        penalty_applied,
+        penalty_refunded,
+        penalty_recovered,
+        outstanding_penalty,
+        open_disputes,
+        resolved_disputes,
+        rate_limits,
+        rating_suspended_until,
+        latest_detections,
+    })
 }
 
 #[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    Ok(Response::default())
+
+    // Re-key history recorded before calculations carried a sequence number;
+    // whatever does not fit is left to `MigrateKarmaHistory`
+    let (migrated_history, history_completed) = migrate_legacy_history(deps.storage, MAX_BACKFILL_LIMIT)?;
+
+    // Configs stored before trust was configurable pre-trust nobody; trust
+    // flows from the admin instead
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.karma_config.trust.pre_trusted.is_empty() {
+        config.karma_config.trust.pre_trusted = vec![config.admin.to_string()];
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    // Oracle data stored before payloads were parsed cannot be converted
+    let cleared_oracle_data = migrate_legacy_oracle_data(deps.storage)?;
+
+    // Earned/spent totals give way to itemised ledgers opened at current karma;
+    // whatever does not fit is left to `MigrateKarmaLedgers`
+    let (opened_ledgers, ledgers_completed) = migrate_legacy_karma_balance(deps.storage, &env, MAX_BACKFILL_LIMIT)?;
+
+    // Aggregate ratings given before aggregates existed; whatever does not fit
+    // is left to `MigrateRatingAggregates`
+    let (aggregated_ratings, aggregates_completed) = migrate_rating_aggregates(deps.storage, MAX_BACKFILL_LIMIT)?;
+
+    // File violations recorded before they were kept per agent; whatever does
+    // not fit is left to `MigrateViolationIndex`
+    let (indexed_violations, violations_completed) = migrate_violation_index(deps.storage, MAX_BACKFILL_LIMIT)?;
+
+    // File disputes filed before they were kept per violation; whatever does
+    // not fit is left to `MigrateDisputeIndex`
+    let (indexed_disputes, disputes_completed) = migrate_dispute_index(deps.storage, MAX_BACKFILL_LIMIT)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("migrated_history", migrated_history.to_string())
+        .add_attribute("history_completed", history_completed.to_string())
+        .add_attribute("cleared_oracle_data", cleared_oracle_data.to_string())
+        .add_attribute("opened_ledgers", opened_ledgers.to_string())
+        .add_attribute("ledgers_completed", ledgers_completed.to_string())
+        .add_attribute("aggregated_ratings", aggregated_ratings.to_string())
+        .add_attribute("aggregates_completed", aggregates_completed.to_string())
+        .add_attribute("indexed_violations", indexed_violations.to_string())
+        .add_attribute("violations_completed", violations_completed.to_string())
+        .add_attribute("indexed_disputes", indexed_disputes.to_string())
+        .add_attribute("disputes_completed", disputes_completed.to_string()))
 }
 
 // Compliance execute functions
@@ -761,52 +2002,38 @@ pub fn execute_run_abuse_detection(
         }
     })?;
 
+    let enforcement = CONFIG.load(deps.storage)?.karma_config.enforcement;
     let mut violations_detected = 0;
     let mut total_penalty = Uint128::zero();
-
-    // Process detection results and apply penalties if needed
+    let mut enforced = vec![];
+
+    // Keep each detector's result, then act on suspicious results under the
+    // enforcement policies
+    for (detector, result) in DETECTORS.iter().zip(detection_results.iter()) {
+        let run = DetectorRun {
+            detected_at: env.block.time,
+            result: detection_result_response(result.clone()),
+        };
+        ABUSE_PATTERNS.save(deps.storage, (agent_addr.as_str(), detector.as_str()), &run)?;
+    }
     for result in &detection_results {
         if result.is_suspicious {
             violations_detected += 1;
-            total_penalty += result.recommended_penalty;
-
-            // Create compliance violation record
-            let violation_id = format!(
-                "{}:{}:{}",
-                agent_address,
-                result
-                    .violation_type
-                    .as_ref()
-                    .map(|v| format!("{:?}", v))
-                    .unwrap_or("unknown".to_string()),
-                env.block.time.seconds()
-            );
-
-            let violation = crate::compliance::ComplianceViolation {
-                agent_address: agent_addr.clone(),
-                violation_type: result
-                    .violation_type
-                    .clone()
-                    .unwrap_or(ViolationType::SuspiciousPattern),
-                severity: (result.confidence_score * 10.0) as u8,
-                timestamp: env.block.time,
-                evidence: result.evidence.join("; "),
-                penalty_applied: result.recommended_penalty,
-                disputed: false,
-            };
-
-            // Save violation record
-            crate::state::COMPLIANCE_VIOLATIONS.save(deps.storage, &violation_id, &violation)?;
-
-            // Apply penalty
-            apply_abuse_penalty(deps.branch(), &env, &agent_addr, &violation)?;
+        }
+        if let Some(outcome) = enforce_detection_result(deps.branch(), &env, &enforcement, &agent_addr, result)? {
+            total_penalty += outcome.penalty_applied;
+            enforced.push(format!("{}:{}", outcome.violation_type.as_str(), action_name(&outcome.action)));
         }
     }
+    if !total_penalty.is_zero() {
+        relist_agent(deps.branch(), &agent_addr)?;
+    }
 
     Ok(Response::new()
         .add_attribute("method", "run_abuse_detection")
         .add_attribute("agent_address", agent_address)
         .add_attribute("violations_detected", violations_detected.to_string())
+        .add_attribute("enforced", enforced.join(","))
         .add_attribute("total_penalty", total_penalty))
 }
 
@@ -840,21 +2067,11 @@ pub fn execute_apply_compliance_penalty(
     let penalty_amount = base_penalty * Uint128::from(severity as u128);
 
     // Parse violation type
-    let violation_enum = match violation_type.as_str() {
-        "spam_rating" => ViolationType::SpamRating,
-        "rating_manipulation" => ViolationType::RatingManipulation,
-        "bot_behavior" => ViolationType::BotBehavior,
-        "rate_limit_exceeded" => ViolationType::RateLimitExceeded,
-        _ => ViolationType::SuspiciousPattern,
-    };
+    let violation_enum = parse_violation_type(&violation_type).unwrap_or(ViolationType::SuspiciousPattern);
 
     // Create violation record
-    let violation_id = format!(
-        "manual:{}:{}:{}",
-        agent_address,
-        violation_type,
-        env.block.time.seconds()
-    );
+    let prefix = format!("manual:{}:{}:{}", agent_address, violation_type, env.block.time.seconds());
+    let violation_id = next_violation_id(deps.storage, &prefix)?;
     let violation = crate::compliance::ComplianceViolation {
         agent_address: agent_addr.clone(),
         violation_type: violation_enum,
@@ -863,32 +2080,60 @@ pub fn execute_apply_compliance_penalty(
         evidence,
         penalty_applied: penalty_amount,
         disputed: false,
+        penalty_refunded: Uint128::zero(),
+        overturned: false,
+        enforcement: None,
+        penalty_recovered: Uint128::zero(),
+        open_dispute: None,
     };
 
     // Save violation record
-    crate::state::COMPLIANCE_VIOLATIONS.save(deps.storage, &violation_id, &violation)?;
+    record_violation(deps.storage, &violation_id, &violation)?;
 
     // Apply penalty
-    apply_abuse_penalty(deps.branch(), &env, &agent_addr, &violation)?;
+    apply_abuse_penalty(deps.branch(), &env, &agent_addr, &violation_id, &violation)?;
+    relist_agent(deps.branch(), &agent_addr)?;
 
     Ok(Response::new()
         .add_attribute("method", "apply_compliance_penalty")
+        .add_attribute("violation_id", violation_id)
         .add_attribute("agent_address", agent_address)
         .add_attribute("violation_type", violation_type)
         .add_attribute("severity", severity.to_string())
         .add_attribute("penalty_applied", penalty_amount))
 }
 
+pub fn execute_rehabilitate_agent(
+    mut deps: DepsMut,
+    env: Env,
+    agent_address: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let agent_addr = deps.api.addr_validate(&agent_address)?;
+
+    let recovered = rehabilitate_agent(deps.branch(), &env, &config.karma_config.rehabilitation, &agent_addr)?;
+    if !recovered.is_zero() {
+        relist_agent(deps.branch(), &agent_addr)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "rehabilitate_agent")
+        .add_attribute("agent_address", agent_address)
+        .add_attribute("penalty_recovered", recovered))
+}
+
 pub fn execute_create_dispute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     violation_id: String,
     stake_amount: Uint128,
     evidence: String,
 ) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
     let case_id = create_dispute(
-        deps,
+        deps.branch(),
         &env,
         &info.sender,
         violation_id.clone(),
@@ -896,16 +2141,66 @@ pub fn execute_create_dispute(
         evidence,
     )?;
 
+    // The stake bypasses karma recalculation; relist the challenger
+    relist_agent(deps.branch(), &info.sender)?;
+
+    // The panel is drawn in a block fixed now, once the dispute is on record
+    let excluded = dispute_parties(deps.storage, &case_id)?;
+    let juror_draw = schedule_juror_draw(
+        deps.storage,
+        &env,
+        &config.karma_config.arbitration,
+        &case_id,
+        &excluded.iter().collect::<Vec<_>>(),
+    )?;
+
     Ok(Response::new()
         .add_attribute("method", "create_dispute")
         .add_attribute("challenger", info.sender)
         .add_attribute("violation_id", violation_id)
         .add_attribute("case_id", case_id)
-        .add_attribute("stake_amount", stake_amount))
+        .add_attribute("stake_amount", stake_amount)
+        .add_attribute(
+            "juror_draw_height",
+            juror_draw.map(|height| height.to_string()).unwrap_or_default(),
+        ))
+}
+
+pub fn execute_draw_jurors(deps: DepsMut, env: Env, case_id: String) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let excluded = dispute_parties(deps.storage, &case_id)?;
+    let draw = open_arbitration(
+        deps.storage,
+        &env,
+        &config.karma_config.arbitration,
+        &case_id,
+        &excluded.iter().collect::<Vec<_>>(),
+    )?;
+
+    let response = Response::new()
+        .add_attribute("method", "draw_jurors")
+        .add_attribute("case_id", case_id);
+    Ok(match draw {
+        JurorDraw::Drawn(jurors) => {
+            response.add_attribute("jurors", jurors.iter().map(Addr::as_str).collect::<Vec<_>>().join(","))
+        }
+        JurorDraw::Rescheduled(draw_height) => response.add_attribute("juror_draw_height", draw_height.to_string()),
+    })
+}
+
+/// Challenger and violator of a dispute, neither of whom may sit on its panel
+fn dispute_parties(storage: &dyn Storage, case_id: &str) -> StdResult<Vec<Addr>> {
+    let dispute_case = DISPUTE_CASES.load(storage, case_id)?;
+    let violator = COMPLIANCE_VIOLATIONS
+        .may_load(storage, &dispute_case.violation_id)?
+        .map(|violation| violation.agent_address);
+
+    Ok(std::iter::once(dispute_case.challenger).chain(violator).collect())
 }
 
 pub fn execute_resolve_dispute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     case_id: String,
@@ -918,49 +2213,212 @@ pub fn execute_resolve_dispute(
         return Err(ContractError::AdminRequired {});
     }
 
-    let dispute_resolution = match resolution.as_str() {
-        "confirmed" => DisputeResolution::ViolationConfirmed,
-        "overturned" => DisputeResolution::ViolationOverturned,
-        "partial" => DisputeResolution::PartialOverturned,
-        _ => {
-            return Err(ContractError::ComplianceViolation {
-                reason: "Invalid resolution type".to_string(),
-            })
-        }
-    };
+    // Disputes with a juror panel, drawn or pending, are settled by its votes
+    let draw_pending = DISPUTE_CASES
+        .may_load(deps.storage, &case_id)?
+        .is_some_and(|dispute_case| dispute_case.juror_draw_height.is_some());
+    if ARBITRATIONS.has(deps.storage, &case_id) || draw_pending {
+        return Err(ContractError::DisputeUnderArbitration { case_id });
+    }
 
-    resolve_dispute(deps, &env, &case_id, dispute_resolution)?;
+    let dispute_resolution = parse_dispute_resolution(&resolution)?;
+    let outcome = resolve_dispute(deps.branch(), &env, &case_id, dispute_resolution)?;
 
-    Ok(Response::new()
+    let response = Response::new()
         .add_attribute("method", "resolve_dispute")
         .add_attribute("case_id", case_id)
         .add_attribute("resolution", resolution)
-        .add_attribute("resolver", info.sender))
+        .add_attribute("resolver", info.sender);
+
+    settle_dispute_outcome(deps, &env, outcome, response)
 }
 
-/// Update leaderboard with new karma score
-pub fn update_leaderboard(
+pub fn execute_commit_juror_vote(
     deps: DepsMut,
-    agent_address: &Addr,
+    env: Env,
+    info: MessageInfo,
+    case_id: String,
+    commitment: String,
+) -> Result<Response, ContractError> {
+    commit_vote(deps.storage, &env, &case_id, &info.sender, commitment)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "commit_juror_vote")
+        .add_attribute("case_id", case_id)
+        .add_attribute("juror", info.sender))
+}
+
+pub fn execute_reveal_juror_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    case_id: String,
+    resolution: String,
+    salt: String,
+) -> Result<Response, ContractError> {
+    reveal_vote(deps.storage, &env, &case_id, &info.sender, &resolution, &salt)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "reveal_juror_vote")
+        .add_attribute("case_id", case_id)
+        .add_attribute("juror", info.sender)
+        .add_attribute("resolution", resolution))
+}
+
+pub fn execute_finalize_arbitration(
+    mut deps: DepsMut,
+    env: Env,
+    case_id: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    let verdict = finalize_arbitration(deps.branch(), &env, &config.karma_config.arbitration, &case_id)?;
+    let outcome = resolve_dispute(deps.branch(), &env, &case_id, verdict.resolution.clone())?;
+
+    // Juror rewards and penalties bypass karma recalculation; relist the jurors
+    for juror in verdict.rewarded.iter().chain(verdict.penalized.iter()) {
+        relist_agent(deps.branch(), juror)?;
+    }
+
+    let join = |jurors: &[Addr]| jurors.iter().map(Addr::as_str).collect::<Vec<_>>().join(",");
+    let response = Response::new()
+        .add_attribute("method", "finalize_arbitration")
+        .add_attribute("case_id", case_id)
+        .add_attribute("resolution", format!("{:?}", verdict.resolution))
+        .add_attribute("rewarded_jurors", join(&verdict.rewarded))
+        .add_attribute("penalized_jurors", join(&verdict.penalized));
+
+    settle_dispute_outcome(deps, &env, outcome, response)
+}
+
+/// Report a resolved dispute's stake movements, recalculate the refunded
+/// agent's karma and leaderboard entry and relist the challenger
+fn settle_dispute_outcome(
+    mut deps: DepsMut,
+    env: &Env,
+    outcome: DisputeOutcome,
+    response: Response,
+) -> Result<Response, ContractError> {
+    let mut response = response
+        .add_attribute("stake_returned", outcome.stake_returned)
+        .add_attribute("reward", outcome.reward)
+        .add_attribute("penalty_refunded", outcome.penalty_refunded);
+
+    if let Some(agent) = outcome.refunded_agent {
+        let new_karma = refresh_karma(deps.branch(), env, &agent)?;
+        response = response
+            .add_attribute("refunded_agent", agent)
+            .add_attribute("new_karma", new_karma);
+    }
+
+    // Returned stake and rewards bypass karma recalculation
+    if let Some(challenger) = outcome.challenger {
+        relist_agent(deps.branch(), &challenger)?;
+    }
+
+    Ok(response)
+}
+
+/// Move an agent whose karma changed outside recalculation to its new
+/// leaderboard position; frozen agents stay off the leaderboard
+fn relist_agent(mut deps: DepsMut, agent: &Addr) -> Result<(), ContractError> {
+    if !FROZEN_AGENTS.has(deps.storage, agent.as_str()) {
+        let karma = get_agent_karma_score(deps.as_ref(), agent)?;
+        update_leaderboard(deps.branch(), agent, karma)?;
+    }
+
+    Ok(())
+}
+
+/// Recalculate an agent's karma and leaderboard entry and return its karma;
+/// deactivated agents keep their karma frozen
+fn refresh_karma(mut deps: DepsMut, env: &Env, agent: &Addr) -> Result<Uint128, ContractError> {
+    if !sync_agent_status(deps.branch(), env, agent)? {
+        return Ok(get_agent_karma_score(deps.as_ref(), agent)?);
+    }
+
+    let karma_calculation = calculate_karma_score(deps.as_ref(), env, agent)?;
+    let new_karma = update_karma_score(deps.branch(), env, agent, &karma_calculation)?;
+    update_leaderboard(deps.branch(), agent, new_karma)?;
+
+    Ok(new_karma)
+}
+
+/// Leaderboard key for a score: ascending key order lists the highest scores first
+fn leaderboard_key(score: u128) -> u128 {
+    u128::MAX - score
+}
+
+/// Fold a rating into the rated agent's karma for the interaction's type
+fn update_category_karma(
+    deps: DepsMut,
+    env: &Env,
+    rating: &Rating,
+    interaction_type: &str,
+    rater_karma: Uint128,
+) -> Result<(), ContractError> {
+    let agent_str = rating.rated_address.as_str();
+
+    let aggregates = record_category_rating(deps.storage, rating, interaction_type, rater_karma)?;
+    let karma = compute_category_karma(
+        deps.as_ref(),
+        env,
+        &rating.rated_address,
+        interaction_type,
+        &aggregates,
+    )?;
+    CATEGORY_KARMA.save(deps.storage, (agent_str, interaction_type), &karma)?;
+
+    // Move the agent within the category leaderboard
+    update_category_leaderboard(deps.storage, interaction_type, agent_str, karma.current_score)
+}
+
+/// List an agent under a new score in a category leaderboard; a zero score
+/// removes it
+fn update_category_leaderboard(
+    storage: &mut dyn Storage,
+    interaction_type: &str,
+    agent: &str,
     new_karma_score: Uint128,
 ) -> Result<(), ContractError> {
-    let agent_str = agent_address.to_string();
+    if let Some(old_score) = CATEGORY_LEADERBOARD_SCORES.may_load(storage, (interaction_type, agent))? {
+        CATEGORY_LEADERBOARD.remove(storage, (interaction_type, leaderboard_key(old_score), agent));
+        CATEGORY_LEADERBOARD_SCORES.remove(storage, (interaction_type, agent));
+    }
+    if !new_karma_score.is_zero() {
+        let score = new_karma_score.u128();
+        CATEGORY_LEADERBOARD.save(storage, (interaction_type, leaderboard_key(score), agent), &())?;
+        CATEGORY_LEADERBOARD_SCORES.save(storage, (interaction_type, agent), &score)?;
+    }
 
-    // Remove old entry if it exists
-    let old_karma = KARMA_SCORES
-        .may_load(deps.storage, &agent_str)?
-        .map(|k| k.current_score.u128())
-        .unwrap_or(0);
+    Ok(())
+}
 
-    if old_karma > 0 {
-        LEADERBOARD.remove(deps.storage, old_karma);
+pub fn update_leaderboard(
+    deps: DepsMut,
+    agent_address: &Addr,
+    new_karma_score: Uint128,
+) -> Result<(), ContractError> {
+    let agent_str = agent_address.as_str();
+    let mut size = LEADERBOARD_SIZE.may_load(deps.storage)?.unwrap_or(0);
+
+    // Remove the agent's own entry, whatever score it was listed under
+    if let Some(old_score) = LEADERBOARD_SCORES.may_load(deps.storage, agent_str)? {
+        LEADERBOARD.remove(deps.storage, (leaderboard_key(old_score), agent_str));
+        LEADERBOARD_SCORES.remove(deps.storage, agent_str);
+        size -= 1;
     }
 
     // Add new entry
-    if new_karma_score.u128() > 0 {
-        LEADERBOARD.save(deps.storage, new_karma_score.u128(), &agent_str)?;
+    if !new_karma_score.is_zero() {
+        let score = new_karma_score.u128();
+        LEADERBOARD.save(deps.storage, (leaderboard_key(score), agent_str), &())?;
+        LEADERBOARD_SCORES.save(deps.storage, agent_str, &score)?;
+        size += 1;
     }
 
+    LEADERBOARD_SIZE.save(deps.storage, &size)?;
+
     Ok(())
 }
 
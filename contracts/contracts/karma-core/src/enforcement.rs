@@ -0,0 +1,150 @@
+use cosmwasm_std::{Addr, Decimal, DepsMut, Env, StdResult, Storage, Timestamp, Uint128};
+use crate::compliance::{
+    apply_abuse_penalty, next_violation_id, parse_violation_type, record_violation, AbuseDetectionResult,
+    ComplianceViolation, ViolationType,
+};
+use crate::error::ContractError;
+use crate::state::{ENFORCEMENT_COOLDOWNS, RATING_SUSPENSIONS};
+use agent_karma_contracts::types::{EnforcementAction, EnforcementConfig, EnforcementPolicy};
+
+/// Violation recorded by an enforcement policy
+#[derive(Clone, Debug, PartialEq)]
+pub struct Enforcement {
+    pub violation_id: String,
+    pub violation_type: ViolationType,
+    pub action: EnforcementAction,
+    pub penalty_applied: Uint128,
+}
+
+/// Validate enforcement policies before they are stored
+pub fn validate_enforcement_config(config: &EnforcementConfig) -> Result<(), ContractError> {
+    let invalid = |reason: String| Err(ContractError::InvalidKarmaConfig { reason });
+
+    // Without a cooldown, every detector run in a block would enforce the
+    // same evidence again
+    if config.cooldown == 0 {
+        return invalid("Enforcement cooldown cannot be zero".to_string());
+    }
+
+    for policy in config.policies.iter() {
+        if parse_violation_type(&policy.violation_type).is_none() {
+            return invalid(format!("Unknown violation type \"{}\" in enforcement policy", policy.violation_type));
+        }
+        if policy.min_confidence > Decimal::one() {
+            return invalid(format!("Confidence of \"{}\" enforcement policy cannot exceed 1", policy.violation_type));
+        }
+        if policy.action == (EnforcementAction::SuspendRating { duration: 0 }) {
+            return invalid(format!("Rating suspension of \"{}\" enforcement policy cannot be zero", policy.violation_type));
+        }
+    }
+
+    Ok(())
+}
+
+/// Name of an enforcement action in event attributes
+pub fn action_name(action: &EnforcementAction) -> &'static str {
+    match action {
+        EnforcementAction::Warn => "warn",
+        EnforcementAction::Penalize => "penalize",
+        EnforcementAction::SuspendRating { .. } => "suspend_rating",
+        EnforcementAction::Escalate => "escalate",
+    }
+}
+
+/// Policy with the highest confidence threshold a suspicious detection
+/// result meets, if any
+pub fn matching_policy<'a>(
+    config: &'a EnforcementConfig,
+    result: &AbuseDetectionResult,
+) -> Option<&'a EnforcementPolicy> {
+    if !result.is_suspicious {
+        return None;
+    }
+
+    let violation_type = detected_violation_type(result);
+    let confidence = Decimal::permille((result.confidence_score.clamp(0.0, 1.0) * 1000.0).round() as u64);
+    config
+        .policies
+        .iter()
+        .filter(|policy| policy.violation_type == violation_type.as_str() && policy.min_confidence <= confidence)
+        .max_by_key(|policy| policy.min_confidence)
+}
+
+/// Record a violation for a detection result and act on it under the
+/// matching enforcement policy
+///
+/// Nothing is recorded when no policy matches, or when the agent was already
+/// enforced against for the violation type within the cooldown.
+pub fn enforce_detection_result(
+    mut deps: DepsMut,
+    env: &Env,
+    config: &EnforcementConfig,
+    agent_address: &Addr,
+    result: &AbuseDetectionResult,
+) -> Result<Option<Enforcement>, ContractError> {
+    let Some(policy) = matching_policy(config, result) else {
+        return Ok(None);
+    };
+
+    let violation_type = detected_violation_type(result);
+    let cooldown_key = (agent_address.as_str(), violation_type.as_str());
+    if let Some(last_enforced) = ENFORCEMENT_COOLDOWNS.may_load(deps.storage, cooldown_key)? {
+        if env.block.time < last_enforced.plus_seconds(config.cooldown) {
+            return Ok(None);
+        }
+    }
+
+    let penalty_applied = match policy.action {
+        EnforcementAction::Penalize => result.recommended_penalty,
+        _ => Uint128::zero(),
+    };
+    let prefix = format!("{}:{:?}:{}", agent_address, violation_type, env.block.time.seconds());
+    let violation_id = next_violation_id(deps.storage, &prefix)?;
+    let violation = ComplianceViolation {
+        agent_address: agent_address.clone(),
+        violation_type: violation_type.clone(),
+        severity: (result.confidence_score * 10.0) as u8,
+        timestamp: env.block.time,
+        evidence: result.evidence.join("; "),
+        penalty_applied,
+        disputed: false,
+        penalty_refunded: Uint128::zero(),
+        overturned: false,
+        enforcement: Some(policy.action.clone()),
+        penalty_recovered: Uint128::zero(),
+        open_dispute: None,
+    };
+    record_violation(deps.storage, &violation_id, &violation)?;
+
+    match policy.action {
+        EnforcementAction::Penalize => {
+            apply_abuse_penalty(deps.branch(), env, agent_address, &violation_id, &violation)?;
+        }
+        EnforcementAction::SuspendRating { duration } => {
+            // A running suspension is only ever extended
+            let until = env.block.time.plus_seconds(duration);
+            let current = RATING_SUSPENSIONS.may_load(deps.storage, agent_address.as_str())?;
+            RATING_SUSPENSIONS.save(deps.storage, agent_address.as_str(), &current.map_or(until, |c| c.max(until)))?;
+        }
+        EnforcementAction::Warn | EnforcementAction::Escalate => {}
+    }
+    ENFORCEMENT_COOLDOWNS.save(deps.storage, cooldown_key, &env.block.time)?;
+
+    Ok(Some(Enforcement {
+        violation_id,
+        violation_type,
+        action: policy.action.clone(),
+        penalty_applied,
+    }))
+}
+
+/// End of an agent's rating suspension, if it is still in force at `time`
+pub fn rating_suspension(storage: &dyn Storage, agent: &str, time: Timestamp) -> StdResult<Option<Timestamp>> {
+    Ok(RATING_SUSPENSIONS
+        .may_load(storage, agent)?
+        .filter(|until| *until > time))
+}
+
+fn detected_violation_type(result: &AbuseDetectionResult) -> ViolationType {
+    result.violation_type.clone().unwrap_or(ViolationType::SuspiciousPattern)
+}
@@ -27,6 +27,33 @@ pub enum ContractError {
     #[error("Interaction not found: {interaction_hash}")]
     InteractionNotFound { interaction_hash: String },
 
+    #[error("{address} did not take part in interaction {interaction_hash}")]
+    NotInteractionParticipant { address: String, interaction_hash: String },
+
+    #[error("At most {max} ratings per interaction: {interaction_hash}")]
+    MaxRatingsPerInteractionReached { interaction_hash: String, max: u8 },
+
+    #[error("Rating not found: {rating_id}")]
+    RatingNotFound { rating_id: String },
+
+    #[error("Grace period for amending rating {rating_id} has ended")]
+    RatingGracePeriodExpired { rating_id: String },
+
+    #[error("Rating {rating_id} already has a reply")]
+    RatingAlreadyAnswered { rating_id: String },
+
+    #[error("Invalid reply: {reason}")]
+    InvalidRatingReply { reason: String },
+
+    #[error("Rating {rating_id} has no feedback to flag")]
+    NoFeedbackToFlag { rating_id: String },
+
+    #[error("Rating aggregates of {agent} are still being migrated")]
+    RatingAggregatesPending { agent: String },
+
+    #[error("Feedback of rating {rating_id} already flagged by {address}")]
+    FeedbackAlreadyFlagged { rating_id: String, address: String },
+
     #[error("Cannot rate yourself")]
     CannotRateSelf {},
 
@@ -42,6 +69,9 @@ pub enum ContractError {
     #[error("Oracle data verification failed")]
     OracleDataVerificationFailed {},
 
+    #[error("Invalid {data_type} oracle data: {reason}")]
+    InvalidOracleData { data_type: String, reason: String },
+
     #[error("Invalid time decay factor: {factor}")]
     InvalidTimeDecayFactor { factor: String },
 
@@ -67,12 +97,42 @@ pub enum ContractError {
     #[error("Rate limit exceeded for action: {action}")]
     RateLimitExceeded { action: String },
 
+    #[error("Rating rights of {address} are suspended until {until}")]
+    RatingRightsSuspended { address: String, until: u64 },
+
     #[error("Compliance violation: {reason}")]
     ComplianceViolation { reason: String },
 
     #[error("Dispute case not found: {case_id}")]
     DisputeCaseNotFound { case_id: String },
 
+    #[error("Compliance violation not found: {violation_id}")]
+    ViolationNotFound { violation_id: String },
+
+    #[error("Compliance violation {violation_id} was already overturned")]
+    ViolationAlreadyOverturned { violation_id: String },
+
+    #[error("Compliance violation {violation_id} is already disputed in {case_id}")]
+    ViolationUnderDispute { violation_id: String, case_id: String },
+
+    #[error("Dispute {case_id} is decided by its juror panel")]
+    DisputeUnderArbitration { case_id: String },
+
+    #[error("No juror panel for dispute: {case_id}")]
+    ArbitrationNotFound { case_id: String },
+
+    #[error("{address} is not a juror for dispute {case_id}")]
+    NotAJuror { address: String, case_id: String },
+
+    #[error("The {phase} phase is not open for dispute {case_id}")]
+    ArbitrationPhaseClosed { case_id: String, phase: String },
+
+    #[error("Vote already {action} for dispute {case_id}")]
+    JurorVoteAlreadyRecorded { case_id: String, action: String },
+
+    #[error("Revealed vote does not match the commitment for dispute {case_id}")]
+    InvalidVoteReveal { case_id: String },
+
     #[error("Dispute already resolved: {case_id}")]
     DisputeAlreadyResolved { case_id: String },
 
@@ -1,6 +1,15 @@
-use cosmwasm_std::{Addr, Deps, StdResult, Uint128};
-use crate::state::{KARMA_SCORES, KARMA_BALANCE, CONFIG};
+use cosmwasm_std::{Addr, Deps, Env, StdResult, Uint128};
+use crate::state::{StoredRating, KARMA_SCORES, CONFIG};
 use crate::error::ContractError;
+use crate::ledger::{record_karma_movement, Posting};
+use crate::rate_limit::{consume_quota, rate_limit_config, EARNING_ACTION};
+use agent_karma_contracts::{
+    messages::{
+        agent_registry::{self, AgentResponse, IsRegisteredResponse},
+        interaction_logger::{self, InteractionResponse},
+    },
+    types::{Agent, Interaction, KarmaLedgerReason, Rating},
+};
 
 /// Check if an agent has sufficient karma for an operation
 pub fn check_karma_requirement(
@@ -32,8 +41,10 @@ pub fn get_agent_karma_score(deps: Deps, agent_address: &Addr) -> StdResult<Uint
 /// Spend karma for an operation with enhanced validation and tracking
 pub fn spend_karma(
     deps: cosmwasm_std::DepsMut,
+    env: &Env,
     agent_address: &Addr,
     amount: Uint128,
+    posting: Posting,
 ) -> Result<(), ContractError> {
     let agent_str = agent_address.to_string();
     
@@ -64,58 +75,71 @@ pub fn spend_karma(
     karma_score.current_score = karma_score.current_score.checked_sub(amount)?;
     KARMA_SCORES.save(deps.storage, &agent_str, &karma_score)?;
     
-    // Track spending with detailed history
-    let (earned, spent) = KARMA_BALANCE
-        .may_load(deps.storage, &agent_str)?
-        .unwrap_or((Uint128::zero(), Uint128::zero()));
-    
-    let new_spent = spent.checked_add(amount)?;
-    KARMA_BALANCE.save(deps.storage, &agent_str, &(earned, new_spent))?;
+    record_karma_movement(deps.storage, env, &agent_str, current_karma, karma_score.current_score, posting)?;
     
     Ok(())
 }
 
-/// Award karma to an agent with enhanced validation and caps
-pub fn award_karma(
+/// Return karma spent on an operation that was undone
+pub fn refund_karma(
     deps: cosmwasm_std::DepsMut,
+    env: &Env,
     agent_address: &Addr,
     amount: Uint128,
+    posting: Posting,
 ) -> Result<(), ContractError> {
     let agent_str = agent_address.to_string();
+
+    let mut karma_score = KARMA_SCORES
+        .may_load(deps.storage, &agent_str)?
+        .unwrap_or_default();
+    let before = karma_score.current_score;
+    karma_score.current_score = karma_score.current_score.checked_add(amount)?;
+    KARMA_SCORES.save(deps.storage, &agent_str, &karma_score)?;
+
+    record_karma_movement(deps.storage, env, &agent_str, before, karma_score.current_score, posting)?;
+
+    Ok(())
+}
+
+/// Award karma to an agent within the maximum karma and its rolling daily
+/// earning cap, returning the karma actually awarded
+pub fn award_karma(
+    deps: cosmwasm_std::DepsMut,
+    env: &Env,
+    agent_address: &Addr,
+    amount: Uint128,
+    posting: Posting,
+) -> Result<Uint128, ContractError> {
+    let agent_str = agent_address.to_string();
     
-    // Apply daily earning cap to prevent gaming
-    let daily_cap = Uint128::from(100u128);
-    let adjusted_amount = std::cmp::min(amount, daily_cap);
-    
-    // Update karma score with maximum cap enforcement
     let mut karma_score = KARMA_SCORES
         .may_load(deps.storage, &agent_str)?
         .unwrap_or_default();
+    let before = karma_score.current_score;
     
-    let new_score = karma_score.current_score.checked_add(adjusted_amount)?;
+    // Never award past the maximum karma, nor past the earning cap
     let max_karma_cap = Uint128::from(10000u128);
+    let amount = std::cmp::min(amount, max_karma_cap.saturating_sub(before));
+    let config = rate_limit_config(deps.storage)?;
+    let awarded = consume_quota(deps.storage, &config, &agent_str, EARNING_ACTION, amount, env.block.time)?;
     
-    karma_score.current_score = std::cmp::min(new_score, max_karma_cap);
+    karma_score.current_score = before.checked_add(awarded)?;
     KARMA_SCORES.save(deps.storage, &agent_str, &karma_score)?;
     
-    // Track earning with detailed history
-    let (earned, spent) = KARMA_BALANCE
-        .may_load(deps.storage, &agent_str)?
-        .unwrap_or((Uint128::zero(), Uint128::zero()));
-    
-    let new_earned = earned.checked_add(adjusted_amount)?;
-    KARMA_BALANCE.save(deps.storage, &agent_str, &(new_earned, spent))?;
+    record_karma_movement(deps.storage, env, &agent_str, before, karma_score.current_score, posting)?;
     
-    Ok(())
+    Ok(awarded)
 }
 
 /// Enhanced karma earning mechanism for positive ratings
 pub fn earn_karma_from_rating(
     deps: cosmwasm_std::DepsMut,
-    agent_address: &Addr,
-    rating_score: u8,
+    env: &Env,
+    rating: &Rating,
     rater_karma: Uint128,
 ) -> Result<Uint128, ContractError> {
+    let rating_score = rating.score;
     if rating_score < 6 {
         return Ok(Uint128::zero()); // No karma earned for ratings below 6
     }
@@ -140,17 +164,20 @@ pub fn earn_karma_from_rating(
     };
     
     let total_earning = Uint128::from((base_earning + rater_bonus) as u128);
-    award_karma(deps, agent_address, total_earning)?;
+    let posting = Posting::new(KarmaLedgerReason::RatingReward, Some(&rating.rater_address), Some(&rating.id));
+    let awarded = award_karma(deps, env, &rating.rated_address, total_earning, posting)?;
     
-    Ok(total_earning)
+    Ok(awarded)
 }
 
-/// Karma penalty mechanism for poor ratings
+/// Karma penalty mechanism for poor ratings, returning the karma actually
+/// taken
 pub fn apply_karma_penalty(
     deps: cosmwasm_std::DepsMut,
-    agent_address: &Addr,
-    rating_score: u8,
+    env: &Env,
+    rating: &Rating,
 ) -> Result<Uint128, ContractError> {
+    let rating_score = rating.score;
     if rating_score >= 4 {
         return Ok(Uint128::zero()); // No penalty for ratings 4 and above
     }
@@ -164,34 +191,61 @@ pub fn apply_karma_penalty(
     };
     
     let penalty = Uint128::from(penalty_amount as u128);
+    let posting = Posting::new(KarmaLedgerReason::RatingPenalty, Some(&rating.rater_address), Some(&rating.id));
+    debit_karma(deps, env, &rating.rated_address, penalty, posting)
+}
+
+/// Undo the karma a rating earned or cost the rated agent, ahead of its
+/// amendment or retraction
+pub fn reverse_rating_karma(
+    mut deps: cosmwasm_std::DepsMut,
+    env: &Env,
+    stored: &StoredRating,
+) -> Result<(), ContractError> {
+    let rating = &stored.rating;
+    if !stored.karma_awarded.is_zero() {
+        let posting =
+            Posting::new(KarmaLedgerReason::RatingRewardReversal, Some(&rating.rater_address), Some(&rating.id));
+        debit_karma(deps.branch(), env, &rating.rated_address, stored.karma_awarded, posting)?;
+    }
+    if !stored.karma_penalized.is_zero() {
+        let posting =
+            Posting::new(KarmaLedgerReason::RatingPenaltyReversal, Some(&rating.rater_address), Some(&rating.id));
+        refund_karma(deps, env, &rating.rated_address, stored.karma_penalized, posting)?;
+    }
     
-    // Apply penalty (but don't go below zero)
+    Ok(())
+}
+
+/// Take karma from an agent, never below zero, returning the karma taken
+fn debit_karma(
+    deps: cosmwasm_std::DepsMut,
+    env: &Env,
+    agent_address: &Addr,
+    amount: Uint128,
+    posting: Posting,
+) -> Result<Uint128, ContractError> {
     let agent_str = agent_address.to_string();
     let mut karma_score = KARMA_SCORES
         .may_load(deps.storage, &agent_str)?
         .unwrap_or_default();
-    
-    if karma_score.current_score >= penalty {
-        karma_score.current_score = karma_score.current_score.checked_sub(penalty)?;
-    } else {
-        karma_score.current_score = Uint128::zero();
-    }
-    
+    let before = karma_score.current_score;
+    karma_score.current_score = before.saturating_sub(amount);
     KARMA_SCORES.save(deps.storage, &agent_str, &karma_score)?;
     
-    Ok(penalty)
+    record_karma_movement(deps.storage, env, &agent_str, before, karma_score.current_score, posting)?;
+    
+    Ok(before - karma_score.current_score)
 }
 
-/// Check if an agent is registered (by querying agent registry)
+/// Check that an agent is registered and active in the agent registry
 pub fn verify_agent_registered(
     deps: Deps,
     agent_address: &Addr,
 ) -> Result<(), ContractError> {
-    // In a real implementation, this would query the agent registry contract
-    // For now, we'll assume all agents with karma scores are registered
-    let karma_exists = KARMA_SCORES.has(deps.storage, &agent_address.to_string());
+    let config = CONFIG.load(deps.storage)?;
     
-    if !karma_exists {
+    if !query_is_registered(deps, &config.agent_registry, agent_address.as_str())? {
         return Err(ContractError::AgentNotFound {
             address: agent_address.to_string(),
         });
@@ -200,6 +254,56 @@ pub fn verify_agent_registered(
     Ok(())
 }
 
+/// Ask the agent registry whether an agent is registered and active
+pub fn query_is_registered(
+    deps: Deps,
+    agent_registry: &Addr,
+    agent_address: &str,
+) -> StdResult<bool> {
+    let response: IsRegisteredResponse = deps.querier.query_wasm_smart(
+        agent_registry,
+        &agent_registry::QueryMsg::IsRegistered {
+            agent_address: agent_address.to_string(),
+        },
+    )?;
+
+    Ok(response.registered)
+}
+
+/// Look up an agent in the agent registry
+///
+/// Returns `None` when the agent is not registered or has been deactivated.
+pub fn query_registered_agent(
+    deps: Deps,
+    agent_registry: &Addr,
+    agent_address: &str,
+) -> StdResult<Option<Agent>> {
+    let response: AgentResponse = deps.querier.query_wasm_smart(
+        agent_registry,
+        &agent_registry::QueryMsg::GetAgent {
+            agent_address: agent_address.to_string(),
+        },
+    )?;
+
+    Ok(response.agent)
+}
+
+/// Look up a logged interaction in the interaction logger
+pub fn query_logged_interaction(
+    deps: Deps,
+    interaction_logger: &Addr,
+    interaction_hash: &str,
+) -> StdResult<Option<Interaction>> {
+    let response: InteractionResponse = deps.querier.query_wasm_smart(
+        interaction_logger,
+        &interaction_logger::QueryMsg::GetInteraction {
+            interaction_hash: interaction_hash.to_string(),
+        },
+    )?;
+
+    Ok(response.interaction)
+}
+
 /// Generate unique rating ID
 pub fn generate_rating_id(
     rater: &Addr,
@@ -260,6 +364,7 @@ pub fn check_minimum_requirements(
         "rating" => config.karma_config.min_karma_for_rating,
         "voting" => config.karma_config.min_karma_for_voting,
         "proposal" => config.karma_config.min_karma_for_proposal,
+        "flagging" => config.karma_config.min_karma_for_flagging,
         _ => Uint128::zero(),
     };
     
@@ -284,7 +389,7 @@ impl Default for crate::state::KarmaScore {
             previous_score: Uint128::zero(),
             last_updated: cosmwasm_std::Timestamp::from_seconds(0),
             total_ratings: 0,
-            average_rating: "0.0".to_string(),
+            average_rating: cosmwasm_std::Decimal::zero(),
             interaction_count: 0,
         }
     }
@@ -0,0 +1,217 @@
+use cosmwasm_std::{Order, StdResult, Storage, Timestamp};
+use cw_storage_plus::Bound;
+use crate::state::{KARMA_HISTORY, KARMA_HISTORY_BUCKETS, KARMA_HISTORY_SEQUENCE, LEGACY_KARMA_HISTORY};
+use agent_karma_contracts::{
+    messages::karma_core::KarmaHistoryCursor,
+    types::{HistoryInterval, KarmaCalculation, KarmaHistoryBucket},
+};
+
+/// Most expired entries compacted by a single write, keeping its gas bounded
+const MAX_COMPACTIONS_PER_WRITE: usize = 20;
+
+/// Width of a compacted summary (one day in seconds)
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Append a calculation to an agent's history
+///
+/// With a non-zero `retention`, entries older than `retention` seconds are
+/// then folded into daily summaries, oldest first.
+pub fn record_history(
+    storage: &mut dyn Storage,
+    agent: &str,
+    time: Timestamp,
+    calculation: &KarmaCalculation,
+    retention: u64,
+) -> StdResult<()> {
+    let sequence = KARMA_HISTORY_SEQUENCE.may_load(storage)?.unwrap_or(0) + 1;
+    KARMA_HISTORY_SEQUENCE.save(storage, &sequence)?;
+    KARMA_HISTORY.save(storage, (agent, time.seconds(), sequence), calculation)?;
+
+    if retention > 0 {
+        compact_history(storage, agent, time.seconds().saturating_sub(retention))?;
+    }
+
+    Ok(())
+}
+
+/// Fold entries recorded before `cutoff` into daily summaries
+///
+/// Entries are compacted oldest first, so every summary predates every
+/// remaining entry.
+fn compact_history(storage: &mut dyn Storage, agent: &str, cutoff: u64) -> StdResult<()> {
+    let expired = KARMA_HISTORY
+        .sub_prefix(agent)
+        .range(storage, None, Some(Bound::exclusive((cutoff, 0u64))), Order::Ascending)
+        .take(MAX_COMPACTIONS_PER_WRITE)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for ((time, sequence), calculation) in expired {
+        let day = time - time % SECONDS_PER_DAY;
+        let sample = KarmaHistoryBucket {
+            start: Timestamp::from_seconds(day),
+            min: calculation.current_score,
+            max: calculation.current_score,
+            close: calculation.current_score,
+            samples: 1,
+        };
+
+        let summary = match KARMA_HISTORY_BUCKETS.may_load(storage, (agent, day))? {
+            Some(mut summary) => {
+                merge_bucket(&mut summary, &sample);
+                summary
+            }
+            None => sample,
+        };
+        KARMA_HISTORY_BUCKETS.save(storage, (agent, day), &summary)?;
+        KARMA_HISTORY.remove(storage, (agent, time, sequence));
+    }
+
+    Ok(())
+}
+
+/// A page of raw history between `from` and `to` (inclusive), oldest first,
+/// with the cursor to resume from when more entries remain
+pub fn history_page(
+    storage: &dyn Storage,
+    agent: &str,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    start_after: Option<KarmaHistoryCursor>,
+    limit: usize,
+) -> StdResult<(Vec<KarmaCalculation>, Option<KarmaHistoryCursor>)> {
+    let min = match (start_after, from) {
+        (Some(cursor), _) => Some(Bound::exclusive((cursor.time.seconds(), cursor.sequence))),
+        (None, Some(from)) => Some(Bound::inclusive((from.seconds(), 0u64))),
+        (None, None) => None,
+    };
+    let max = to.map(|to| Bound::inclusive((to.seconds(), u64::MAX)));
+
+    let mut entries = KARMA_HISTORY
+        .sub_prefix(agent)
+        .range(storage, min, max, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let next_cursor = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|((time, sequence), _)| KarmaHistoryCursor {
+            time: Timestamp::from_seconds(*time),
+            sequence: *sequence,
+        })
+    } else {
+        None
+    };
+
+    Ok((entries.into_iter().map(|(_, calculation)| calculation).collect(), next_cursor))
+}
+
+/// Downsample history between `from` and `to` (inclusive) into buckets
+/// aligned to the unix epoch, oldest first
+///
+/// Compacted daily summaries are merged into the buckets their day starts in.
+/// `start_after` is the start of the last bucket of the previous page.
+pub fn history_buckets(
+    storage: &dyn Storage,
+    agent: &str,
+    interval: HistoryInterval,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    start_after: Option<Timestamp>,
+    limit: usize,
+) -> StdResult<Vec<KarmaHistoryBucket>> {
+    let width = interval.seconds();
+    let mut lower = from.map(|from| from.seconds()).unwrap_or(0);
+    if let Some(start_after) = start_after {
+        lower = std::cmp::max(lower, start_after.seconds() + width);
+    }
+    let upper = to.map(|to| to.seconds()).unwrap_or(u64::MAX);
+    if lower > upper {
+        return Ok(vec![]);
+    }
+
+    let summaries = KARMA_HISTORY_BUCKETS
+        .prefix(agent)
+        .range(storage, Some(Bound::inclusive(lower)), Some(Bound::inclusive(upper)), Order::Ascending)
+        .map(|item| item.map(|(_, summary)| summary));
+    let entries = KARMA_HISTORY
+        .sub_prefix(agent)
+        .range(
+            storage,
+            Some(Bound::inclusive((lower, 0u64))),
+            Some(Bound::inclusive((upper, u64::MAX))),
+            Order::Ascending,
+        )
+        .map(|item| {
+            item.map(|((time, _), calculation)| KarmaHistoryBucket {
+                start: Timestamp::from_seconds(time),
+                min: calculation.current_score,
+                max: calculation.current_score,
+                close: calculation.current_score,
+                samples: 1,
+            })
+        });
+
+    let mut buckets: Vec<KarmaHistoryBucket> = vec![];
+    for sample in summaries.chain(entries) {
+        let sample = sample?;
+        let start = sample.start.seconds() - sample.start.seconds() % width;
+
+        match buckets.last_mut() {
+            Some(bucket) if bucket.start.seconds() == start => merge_bucket(bucket, &sample),
+            _ => {
+                if buckets.len() == limit {
+                    break;
+                }
+                buckets.push(KarmaHistoryBucket {
+                    start: Timestamp::from_seconds(start),
+                    ..sample
+                });
+            }
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// Move up to `limit` entries recorded under the old (agent, timestamp) key
+/// into the sequenced series; returns the number moved and whether none are
+/// left
+///
+/// Entries are removed once moved, so each call picks up where the last one
+/// stopped.
+pub fn migrate_legacy_history(storage: &mut dyn Storage, limit: u32) -> StdResult<(u32, bool)> {
+    let mut legacy = LEGACY_KARMA_HISTORY
+        .range(storage, None, None, Order::Ascending)
+        .take(limit as usize + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let completed = legacy.len() <= limit as usize;
+    legacy.truncate(limit as usize);
+
+    let mut sequence = KARMA_HISTORY_SEQUENCE.may_load(storage)?.unwrap_or(0);
+    for ((agent, time), calculation) in legacy.iter() {
+        sequence += 1;
+        KARMA_HISTORY.save(storage, (agent.as_str(), *time, sequence), calculation)?;
+        LEGACY_KARMA_HISTORY.remove(storage, (agent.as_str(), *time));
+    }
+    KARMA_HISTORY_SEQUENCE.save(storage, &sequence)?;
+
+    Ok((legacy.len() as u32, completed))
+}
+
+/// Add a later sample to a bucket
+fn merge_bucket(bucket: &mut KarmaHistoryBucket, sample: &KarmaHistoryBucket) {
+    bucket.min = std::cmp::min(bucket.min, sample.min);
+    bucket.max = std::cmp::max(bucket.max, sample.max);
+    bucket.close = sample.close;
+    bucket.samples += sample.samples;
+}
+
+/// Latest calculation in an agent's history
+pub fn latest_history(storage: &dyn Storage, agent: &str) -> StdResult<Option<KarmaCalculation>> {
+    KARMA_HISTORY
+        .sub_prefix(agent)
+        .range(storage, None, None, Order::Descending)
+        .next()
+        .transpose()
+        .map(|entry| entry.map(|(_, calculation)| calculation))
+}
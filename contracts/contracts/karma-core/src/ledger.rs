@@ -0,0 +1,142 @@
+use cosmwasm_std::{Addr, Env, Int128, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::Bound;
+use crate::state::{
+    LedgerTotals, KARMA_ADJUSTMENTS, KARMA_LEDGER, KARMA_LEDGER_TOTALS, KARMA_SCORES, LEDGER_MIGRATION,
+    LEGACY_KARMA_BALANCE,
+};
+use agent_karma_contracts::types::{KarmaLedgerEntry, KarmaLedgerReason};
+
+/// What a karma movement is booked against
+#[derive(Clone, Debug, PartialEq)]
+pub struct Posting {
+    pub reason: KarmaLedgerReason,
+    pub counterparty: Option<Addr>,
+    pub related_id: Option<String>,
+}
+
+impl Posting {
+    pub fn new(reason: KarmaLedgerReason, counterparty: Option<&Addr>, related_id: Option<&str>) -> Self {
+        Posting {
+            reason,
+            counterparty: counterparty.cloned(),
+            related_id: related_id.map(str::to_string),
+        }
+    }
+}
+
+/// Book the move of an agent's karma from `before` to `after` in its ledger
+///
+/// Nothing is booked when the karma did not change. Movements other than
+/// recalculations, settled decay and opening balances are also added to the
+/// agent's `KARMA_ADJUSTMENTS`, so the next recalculation keeps them.
+pub fn record_karma_movement(
+    storage: &mut dyn Storage,
+    env: &Env,
+    agent: &str,
+    before: Uint128,
+    after: Uint128,
+    posting: Posting,
+) -> StdResult<()> {
+    if before == after {
+        return Ok(());
+    }
+
+    // Agents the ledger migration has yet to reach open their ledger with the
+    // karma they already held
+    let mut totals = KARMA_LEDGER_TOTALS.may_load(storage, agent)?.unwrap_or_default();
+    if totals.entries == 0 && !before.is_zero() && LEDGER_MIGRATION.exists(storage) {
+        let posting = Posting::new(KarmaLedgerReason::OpeningBalance, None, None);
+        record_karma_movement(storage, env, agent, Uint128::zero(), before, posting)?;
+        totals = KARMA_LEDGER_TOTALS.load(storage, agent)?;
+    }
+    if !matches!(
+        posting.reason,
+        KarmaLedgerReason::Recalculation | KarmaLedgerReason::Decay | KarmaLedgerReason::OpeningBalance
+    ) {
+        let movement = Int128::new(after.u128() as i128 - before.u128() as i128);
+        let adjustment = KARMA_ADJUSTMENTS.may_load(storage, agent)?.unwrap_or_default();
+        KARMA_ADJUSTMENTS.save(storage, agent, &(adjustment + movement))?;
+    }
+
+    let entry = KarmaLedgerEntry {
+        sequence: totals.entries,
+        reason: posting.reason,
+        credit: after.saturating_sub(before),
+        debit: before.saturating_sub(after),
+        balance: after,
+        counterparty: posting.counterparty,
+        related_id: posting.related_id,
+        block_height: env.block.height,
+        timestamp: env.block.time,
+    };
+    KARMA_LEDGER.save(storage, (agent, entry.sequence), &entry)?;
+
+    totals.entries += 1;
+    totals.credited += entry.credit;
+    totals.debited += entry.debit;
+    KARMA_LEDGER_TOTALS.save(storage, agent, &totals)
+}
+
+/// One page of an agent's ledger, oldest first, and its running totals
+pub fn ledger_page(
+    storage: &dyn Storage,
+    agent: &str,
+    start_after: Option<u64>,
+    limit: usize,
+) -> StdResult<(Vec<KarmaLedgerEntry>, LedgerTotals)> {
+    let entries = KARMA_LEDGER
+        .prefix(agent)
+        .range(storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, entry)| entry))
+        .collect::<StdResult<Vec<_>>>()?;
+    let totals = KARMA_LEDGER_TOTALS.may_load(storage, agent)?.unwrap_or_default();
+
+    Ok((entries, totals))
+}
+
+/// Open the ledgers of up to `limit` more agents holding karma with their
+/// current score, and drop up to `limit` of the earned/spent totals kept
+/// before movements were itemised; returns the number of ledgers opened and
+/// whether the migration is complete
+///
+/// Agents whose ledger is already open are left alone.
+pub fn migrate_legacy_karma_balance(storage: &mut dyn Storage, env: &Env, limit: u32) -> StdResult<(u32, bool)> {
+    let limit = limit as usize;
+
+    // Open ledgers from karma scores, resuming after the last agent reached
+    let start_after = LEDGER_MIGRATION.may_load(storage)?;
+    let mut scores = KARMA_SCORES
+        .range(storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit + 1)
+        .map(|item| item.map(|(agent, karma)| (agent, karma.current_score)))
+        .collect::<StdResult<Vec<_>>>()?;
+    let reached_all = scores.len() <= limit;
+    scores.truncate(limit);
+
+    let mut opened = 0;
+    for (agent, score) in scores {
+        LEDGER_MIGRATION.save(storage, &agent)?;
+        if score.is_zero() || KARMA_LEDGER_TOTALS.has(storage, &agent) {
+            continue;
+        }
+        let posting = Posting::new(KarmaLedgerReason::OpeningBalance, None, None);
+        record_karma_movement(storage, env, &agent, Uint128::zero(), score, posting)?;
+        opened += 1;
+    }
+    if reached_all {
+        LEDGER_MIGRATION.remove(storage);
+    }
+
+    let mut legacy = LEGACY_KARMA_BALANCE
+        .keys(storage, None, None, Order::Ascending)
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let cleared_all = legacy.len() <= limit;
+    legacy.truncate(limit);
+    for agent in legacy.iter() {
+        LEGACY_KARMA_BALANCE.remove(storage, agent);
+    }
+
+    Ok((opened, reached_all && cleared_all))
+}
@@ -2,8 +2,19 @@ pub mod contract;
 pub mod error;
 pub mod state;
 pub mod karma;
+pub mod algorithm;
+pub mod aggregates;
+pub mod trust;
+pub mod history;
+pub mod oracle;
 pub mod helpers;
+pub mod ledger;
+pub mod rate_limit;
 pub mod compliance;
+pub mod arbitration;
+pub mod enforcement;
+pub mod rehabilitation;
+pub mod simulation;
 
 pub use crate::error::ContractError;
 
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{from_json, Decimal, Order, StdResult, Storage, Timestamp};
+use crate::algorithm::OracleSignals;
+use crate::error::ContractError;
+use crate::karma::half_life_decay;
+use crate::state::{OracleReading, LEGACY_ORACLE_DATA, ORACLE_DATA};
+use agent_karma_contracts::types::{OracleData, OraclePayload};
+
+/// Freshness half-life: a reading this old (7 days, in seconds) counts half
+pub const ORACLE_FRESHNESS_HALF_LIFE: u64 = 7 * 24 * 60 * 60;
+
+/// Highest score an oracle payload may report
+const MAX_ORACLE_SCORE: u32 = 100;
+
+/// Parse and store an oracle submission as the agent's latest reading of
+/// its data type
+///
+/// Submissions dated in the future, or older than the reading they would
+/// replace, are rejected.
+pub fn record_oracle_data(
+    storage: &mut dyn Storage,
+    agent: &str,
+    data: &OracleData,
+    now: Timestamp,
+) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidOracleData {
+        data_type: data.data_type.clone(),
+        reason: reason.to_string(),
+    };
+
+    let payload = parse_oracle_payload(&data.data_type, &data.data)?;
+
+    if payload.agent_id().is_some_and(|agent_id| agent_id != agent) {
+        return Err(invalid("payload describes a different agent"));
+    }
+    if data.timestamp > now {
+        return Err(invalid("submission timestamp is in the future"));
+    }
+
+    let key = (agent, payload.data_type());
+    if let Some(current) = ORACLE_DATA.may_load(storage, key)? {
+        if data.timestamp < current.submitted_at {
+            return Err(invalid("submission is older than the stored reading"));
+        }
+    }
+
+    ORACLE_DATA.save(
+        storage,
+        key,
+        &OracleReading {
+            provider: data.provider.clone(),
+            payload,
+            submitted_at: data.timestamp,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Decode a JSON payload against the schema of its data type
+pub fn parse_oracle_payload(data_type: &str, data: &str) -> Result<OraclePayload, ContractError> {
+    let invalid = |reason: String| ContractError::InvalidOracleData {
+        data_type: data_type.to_string(),
+        reason,
+    };
+
+    let payload = match data_type {
+        "performance" => from_json(data.as_bytes()).map(OraclePayload::Performance),
+        "cross_chain" => from_json(data.as_bytes()).map(OraclePayload::CrossChain),
+        "sentiment" => from_json(data.as_bytes()).map(OraclePayload::Sentiment),
+        _ => return Err(invalid("unsupported data type".to_string())),
+    }
+    .map_err(|e| invalid(e.to_string()))?;
+
+    if payload.score() > MAX_ORACLE_SCORE {
+        return Err(invalid(format!("score {} exceeds {}", payload.score(), MAX_ORACLE_SCORE)));
+    }
+
+    Ok(payload)
+}
+
+/// Freshness of a reading: one when just submitted, halving every
+/// `ORACLE_FRESHNESS_HALF_LIFE`
+pub fn oracle_freshness(submitted_at: Timestamp, now: Timestamp) -> Decimal {
+    half_life_decay(now.seconds().saturating_sub(submitted_at.seconds()), ORACLE_FRESHNESS_HALF_LIFE)
+}
+
+/// Oracle signals available for an agent, along with the freshness of each,
+/// keyed by data type
+pub fn oracle_signals(
+    storage: &dyn Storage,
+    agent: &str,
+    now: Timestamp,
+) -> StdResult<(OracleSignals, BTreeMap<&'static str, Decimal>)> {
+    let mut signals = OracleSignals::default();
+    let mut freshness = BTreeMap::new();
+
+    for item in ORACLE_DATA.prefix(agent).range(storage, None, None, Order::Ascending) {
+        let (_, reading) = item?;
+        let score = Some(reading.payload.score());
+        match reading.payload {
+            OraclePayload::Performance(_) => signals.performance = score,
+            OraclePayload::CrossChain(_) => signals.cross_chain = score,
+            OraclePayload::Sentiment(_) => signals.sentiment = score,
+        }
+        freshness.insert(reading.payload.data_type(), oracle_freshness(reading.submitted_at, now));
+    }
+
+    Ok((signals, freshness))
+}
+
+/// Drop oracle data stored before payloads were parsed
+///
+/// The legacy entries only kept a timestamp and a length, so there is
+/// nothing to convert; agents pick up real readings on the next submission.
+pub fn migrate_legacy_oracle_data(storage: &mut dyn Storage) -> StdResult<u64> {
+    let keys = LEGACY_ORACLE_DATA
+        .keys(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for (agent, data_type) in keys.iter() {
+        LEGACY_ORACLE_DATA.remove(storage, (agent.as_str(), data_type.as_str()));
+    }
+
+    Ok(keys.len() as u64)
+}
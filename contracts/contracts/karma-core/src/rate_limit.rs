@@ -0,0 +1,175 @@
+use cosmwasm_std::{Order, StdResult, Storage, Timestamp, Uint128};
+use crate::error::ContractError;
+use crate::state::{WindowEvent, ACTION_WINDOWS, CONFIG, KARMA_SCORES};
+use agent_karma_contracts::types::RateLimitConfig;
+
+/// Quota karma earnings are counted against
+pub const EARNING_ACTION: &str = "earning";
+
+/// Window of the earning cap (24 hours in seconds)
+const EARNING_WINDOW: u64 = 24 * 60 * 60;
+
+/// Use of a sliding-window quota
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quota {
+    /// Actions performed, or karma earned, within the window
+    pub used: Uint128,
+    /// Most allowed within the window
+    pub limit: Uint128,
+    /// Window length (in seconds)
+    pub window: u64,
+    /// When the oldest use leaves the window, freeing quota
+    pub resets_at: Option<Timestamp>,
+}
+
+impl Quota {
+    pub fn remaining(&self) -> Uint128 {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+/// Rate limits in force; the defaults apply until the contract is configured
+pub fn rate_limit_config(storage: &dyn Storage) -> StdResult<RateLimitConfig> {
+    Ok(CONFIG
+        .may_load(storage)?
+        .map(|config| config.karma_config.rate_limits)
+        .unwrap_or_default())
+}
+
+/// Validate rate limits before they are stored
+pub fn validate_rate_limit_config(config: &RateLimitConfig) -> Result<(), ContractError> {
+    let invalid = |reason: String| Err(ContractError::InvalidKarmaConfig { reason });
+
+    if config.default_window == 0 {
+        return invalid("Default rate limit window cannot be zero".to_string());
+    }
+    for (i, limit) in config.actions.iter().enumerate() {
+        if limit.action == EARNING_ACTION {
+            return invalid(format!("\"{}\" is reserved for the earning cap", EARNING_ACTION));
+        }
+        if limit.window == 0 {
+            return invalid(format!("Rate limit window of \"{}\" cannot be zero", limit.action));
+        }
+        if config.actions[..i].iter().any(|other| other.action == limit.action) {
+            return invalid(format!("Duplicate rate limit for \"{}\"", limit.action));
+        }
+    }
+
+    Ok(())
+}
+
+/// Current use of an agent's quota for an action, or for earnings
+pub fn quota(
+    storage: &dyn Storage,
+    config: &RateLimitConfig,
+    agent: &str,
+    action: &str,
+    now: Timestamp,
+) -> StdResult<Quota> {
+    let (limit, window) = quota_limit(storage, config, agent, action)?;
+    let events = live_events(storage, agent, action, window, now)?;
+
+    Ok(Quota {
+        used: events.iter().map(|event| event.amount).sum(),
+        limit,
+        window,
+        resets_at: events.first().map(|event| event.at.plus_seconds(window)),
+    })
+}
+
+/// Use up to `amount` of an agent's quota and return how much was granted
+pub fn consume_quota(
+    storage: &mut dyn Storage,
+    config: &RateLimitConfig,
+    agent: &str,
+    action: &str,
+    amount: Uint128,
+    now: Timestamp,
+) -> StdResult<Uint128> {
+    let (limit, window) = quota_limit(storage, config, agent, action)?;
+    let mut events = live_events(storage, agent, action, window, now)?;
+
+    let used: Uint128 = events.iter().map(|event| event.amount).sum();
+    let granted = std::cmp::min(amount, limit.saturating_sub(used));
+    if !granted.is_zero() {
+        match events.last_mut() {
+            Some(last) if last.at == now => last.amount += granted,
+            _ => events.push(WindowEvent { at: now, amount: granted }),
+        }
+    }
+    ACTION_WINDOWS.save(storage, (agent, action), &events)?;
+
+    Ok(granted)
+}
+
+/// Actions of any type an agent performed after `since`, with the time of
+/// the earliest
+pub fn actions_since(
+    storage: &dyn Storage,
+    agent: &str,
+    since: Timestamp,
+) -> StdResult<(u128, Option<Timestamp>)> {
+    let mut count = 0;
+    let mut earliest: Option<Timestamp> = None;
+
+    for item in ACTION_WINDOWS.prefix(agent).range(storage, None, None, Order::Ascending) {
+        let (action, events) = item?;
+        if action == EARNING_ACTION {
+            continue;
+        }
+        for event in events.iter().filter(|event| event.at > since) {
+            count += event.amount.u128();
+            earliest = Some(earliest.map_or(event.at, |earliest| earliest.min(event.at)));
+        }
+    }
+
+    Ok((count, earliest))
+}
+
+/// Limit and window of a quota; agents with more karma may act more often
+fn quota_limit(
+    storage: &dyn Storage,
+    config: &RateLimitConfig,
+    agent: &str,
+    action: &str,
+) -> StdResult<(Uint128, u64)> {
+    if action == EARNING_ACTION {
+        return Ok((config.daily_earning_cap, EARNING_WINDOW));
+    }
+
+    let (max_actions, window) = config
+        .actions
+        .iter()
+        .find(|limit| limit.action == action)
+        .map_or((config.default_max_actions, config.default_window), |limit| {
+            (limit.max_actions, limit.window)
+        });
+
+    let karma = KARMA_SCORES.may_load(storage, agent)?.unwrap_or_default().current_score;
+    let multiplier_percent: u128 = if karma > Uint128::new(1000) {
+        200
+    } else if karma > Uint128::new(500) {
+        150
+    } else if karma > Uint128::new(100) {
+        120
+    } else {
+        100
+    };
+
+    Ok((Uint128::from(max_actions).multiply_ratio(multiplier_percent, 100u128), window))
+}
+
+/// Uses of a quota still inside the window ending at `now`
+fn live_events(
+    storage: &dyn Storage,
+    agent: &str,
+    action: &str,
+    window: u64,
+    now: Timestamp,
+) -> StdResult<Vec<WindowEvent>> {
+    let start = now.seconds().saturating_sub(window);
+    let mut events = ACTION_WINDOWS.may_load(storage, (agent, action))?.unwrap_or_default();
+    events.retain(|event| event.at.seconds() > start);
+
+    Ok(events)
+}
@@ -0,0 +1,147 @@
+use cosmwasm_std::{Addr, Decimal, DepsMut, Env, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+use crate::compliance::{parse_violation_type, ComplianceViolation};
+use crate::error::ContractError;
+use crate::helpers::refund_karma;
+use crate::ledger::Posting;
+use crate::state::{AGENT_VIOLATIONS, COMPLIANCE_VIOLATIONS, KARMA_PENALTIES, VIOLATION_INDEX_MIGRATION};
+use agent_karma_contracts::types::{KarmaLedgerReason, RehabilitationConfig};
+
+/// Validate violation expiry and penalty recovery before they are stored
+pub fn validate_rehabilitation_config(config: &RehabilitationConfig) -> Result<(), ContractError> {
+    let invalid = |reason: String| Err(ContractError::InvalidKarmaConfig { reason });
+
+    if config.default_expiry == 0 {
+        return invalid("Default violation expiry cannot be zero".to_string());
+    }
+    for (i, expiry) in config.expiry_windows.iter().enumerate() {
+        if parse_violation_type(&expiry.violation_type).is_none() {
+            return invalid(format!("Unknown violation type \"{}\" in expiry windows", expiry.violation_type));
+        }
+        if expiry.window == 0 {
+            return invalid(format!("Expiry window of \"{}\" cannot be zero", expiry.violation_type));
+        }
+        if config.expiry_windows[..i].iter().any(|other| other.violation_type == expiry.violation_type) {
+            return invalid(format!("Duplicate expiry window for \"{}\"", expiry.violation_type));
+        }
+    }
+    if config.recovery_percent > 100 {
+        return invalid("Penalty recovery cannot exceed 100 percent".to_string());
+    }
+    if config.recovery_period == 0 {
+        return invalid("Penalty recovery period cannot be zero".to_string());
+    }
+
+    Ok(())
+}
+
+/// When a violation leaves the agent's active record
+pub fn violation_expires_at(config: &RehabilitationConfig, violation: &ComplianceViolation) -> Timestamp {
+    let window = config
+        .expiry_windows
+        .iter()
+        .find(|expiry| expiry.violation_type == violation.violation_type.as_str())
+        .map_or(config.default_expiry, |expiry| expiry.window);
+    violation.timestamp.plus_seconds(window)
+}
+
+/// Whether a violation is neither expired nor overturned at `time`
+pub fn is_violation_active(config: &RehabilitationConfig, violation: &ComplianceViolation, time: Timestamp) -> bool {
+    !violation.overturned && time < violation_expires_at(config, violation)
+}
+
+/// Violations recorded against an agent, by violation ID
+pub fn agent_violations(storage: &dyn Storage, agent: &str) -> StdResult<Vec<(String, ComplianceViolation)>> {
+    AGENT_VIOLATIONS
+        .prefix(agent)
+        .keys(storage, None, None, Order::Ascending)
+        .map(|violation_id| {
+            let violation_id = violation_id?;
+            let violation = COMPLIANCE_VIOLATIONS.load(storage, &violation_id)?;
+            Ok((violation_id, violation))
+        })
+        .collect()
+}
+
+/// Share of the recoverable penalties an agent whose latest violation was at
+/// `last_violation` has earned back by `time`
+pub fn recovery_progress(config: &RehabilitationConfig, last_violation: Timestamp, time: Timestamp) -> Decimal {
+    let in_good_standing = time
+        .seconds()
+        .saturating_sub(last_violation.seconds())
+        .saturating_sub(config.probation_period);
+    Decimal::from_ratio(in_good_standing.min(config.recovery_period), config.recovery_period)
+}
+
+/// Return the karma an agent has recovered in good standing since it was
+/// last rehabilitated, and the total returned
+///
+/// Overturned violations do not count, as a dispute already refunded them;
+/// a new violation restarts the probation period but never takes back karma
+/// already recovered.
+pub fn rehabilitate_agent(
+    mut deps: DepsMut,
+    env: &Env,
+    config: &RehabilitationConfig,
+    agent_address: &Addr,
+) -> Result<Uint128, ContractError> {
+    let violations: Vec<_> = agent_violations(deps.storage, agent_address.as_str())?
+        .into_iter()
+        .filter(|(_, violation)| !violation.overturned)
+        .collect();
+    let Some(last_violation) = violations.iter().map(|(_, violation)| violation.timestamp).max() else {
+        return Ok(Uint128::zero());
+    };
+    let progress = recovery_progress(config, last_violation, env.block.time);
+
+    let mut recovered = Uint128::zero();
+    for (violation_id, mut violation) in violations {
+        let recoverable = violation
+            .penalty_applied
+            .saturating_sub(violation.penalty_refunded)
+            .multiply_ratio(config.recovery_percent, 100u128);
+        let amount = (recoverable * progress).saturating_sub(violation.penalty_recovered);
+        if amount.is_zero() {
+            continue;
+        }
+
+        violation.penalty_recovered += amount;
+        COMPLIANCE_VIOLATIONS.save(deps.storage, &violation_id, &violation)?;
+        let posting = Posting::new(KarmaLedgerReason::PenaltyRecovery, None, Some(&violation_id));
+        refund_karma(deps.branch(), env, agent_address, amount, posting)?;
+
+        // Take the recovered karma off the recorded penalty
+        if let Some(penalty) = KARMA_PENALTIES.may_load(deps.storage, &violation_id)? {
+            KARMA_PENALTIES.save(deps.storage, &violation_id, &penalty.saturating_sub(amount))?;
+        }
+
+        recovered += amount;
+    }
+
+    Ok(recovered)
+}
+
+/// File up to `limit` more violations recorded before they were kept per
+/// agent under their agent; returns the number of violations filed and
+/// whether every violation was reached
+pub fn migrate_violation_index(storage: &mut dyn Storage, limit: u32) -> StdResult<(u32, bool)> {
+    let start_after = VIOLATION_INDEX_MIGRATION.may_load(storage)?;
+    let violations = COMPLIANCE_VIOLATIONS
+        .range(storage, start_after.as_deref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit as usize + 1)
+        .map(|item| item.map(|(violation_id, violation)| (violation_id, violation.agent_address, violation.timestamp)))
+        .collect::<StdResult<Vec<_>>>()?;
+    let completed = violations.len() <= limit as usize;
+
+    let mut indexed = 0;
+    for (violation_id, agent, timestamp) in violations.into_iter().take(limit as usize) {
+        let key = (agent.as_str(), violation_id.as_str());
+        if !AGENT_VIOLATIONS.has(storage, key) {
+            AGENT_VIOLATIONS.save(storage, key, &timestamp)?;
+            indexed += 1;
+        }
+        VIOLATION_INDEX_MIGRATION.save(storage, &violation_id)?;
+    }
+
+    Ok((indexed, completed))
+}
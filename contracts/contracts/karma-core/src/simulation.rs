@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Order, Record, Storage};
+
+/// Copy-on-write view over read-only storage
+///
+/// Writes land in an in-memory overlay and are discarded with it, which lets
+/// queries run execute handlers end to end (e.g. `SimulateRating`) without
+/// touching contract state.
+pub struct OverlayStorage<'a> {
+    base: &'a dyn Storage,
+    /// Pending writes; `None` marks a removed key
+    overlay: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> OverlayStorage<'a> {
+    pub fn new(base: &'a dyn Storage) -> Self {
+        Self {
+            base,
+            overlay: BTreeMap::new(),
+        }
+    }
+}
+
+impl<'a> Storage for OverlayStorage<'a> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.overlay.get(key) {
+            Some(value) => value.clone(),
+            None => self.base.get(key),
+        }
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        let in_range = |key: &[u8]| {
+            let after_start = match start {
+                Some(start) => key >= start,
+                None => true,
+            };
+            let before_end = match end {
+                Some(end) => key < end,
+                None => true,
+            };
+            after_start && before_end
+        };
+
+        // Merge the base range with the overlay, the overlay taking precedence
+        let mut merged: BTreeMap<Vec<u8>, Vec<u8>> = self
+            .base
+            .range(start, end, Order::Ascending)
+            .collect();
+        for (key, value) in self.overlay.iter().filter(|(key, _)| in_range(key)) {
+            match value {
+                Some(value) => merged.insert(key.clone(), value.clone()),
+                None => merged.remove(key),
+            };
+        }
+
+        match order {
+            Order::Ascending => Box::new(merged.into_iter()),
+            Order::Descending => Box::new(merged.into_iter().rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.overlay.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.overlay.insert(key.to_vec(), None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn test_overlay_reads_through_and_never_writes_back() {
+        let mut base = MockStorage::new();
+        base.set(b"a", b"1");
+        base.set(b"b", b"2");
+        base.set(b"c", b"3");
+
+        let mut overlay = OverlayStorage::new(&base);
+        overlay.set(b"b", b"20");
+        overlay.remove(b"c");
+        overlay.set(b"d", b"4");
+
+        assert_eq!(overlay.get(b"a"), Some(b"1".to_vec()));
+        assert_eq!(overlay.get(b"b"), Some(b"20".to_vec()));
+        assert_eq!(overlay.get(b"c"), None);
+
+        let keys: Vec<Vec<u8>> = overlay
+            .range(Some(b"b"), None, Order::Descending)
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, vec![b"d".to_vec(), b"b".to_vec()]);
+
+        drop(overlay);
+        assert_eq!(base.get(b"b"), Some(b"2".to_vec()));
+        assert_eq!(base.get(b"c"), Some(b"3".to_vec()));
+        assert_eq!(base.get(b"d"), None);
+    }
+}
@@ -1,8 +1,11 @@
-use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Decimal, Int128, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map, MultiIndex, IndexList, IndexedMap, Index};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use agent_karma_contracts::types::{Rating, KarmaCalculation, KarmaConfig, KarmaFactors};
+use agent_karma_contracts::types::{
+    Rating, KarmaCalculation, KarmaConfig, KarmaHistoryBucket, OraclePayload,
+    KarmaLedgerEntry, RatingReply, RatingRevision,
+};
 
 /// Contract configuration
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -26,6 +29,33 @@ pub struct StoredRating {
     pub processed: bool,
     /// Karma fee paid for this rating
     pub fee_paid: Uint128,
+    /// Interaction type of the rated interaction, if the logger knew it
+    #[serde(default)]
+    pub interaction_type: Option<String>,
+    /// Rater's karma when the rating was given; `None` for ratings stored
+    /// before it was kept
+    #[serde(default)]
+    pub rater_karma: Option<Uint128>,
+    /// Earlier versions of the rating, oldest first; only ever appended to
+    #[serde(default)]
+    pub revisions: Vec<RatingRevision>,
+    /// Timestamp when the rater retracted the rating
+    #[serde(default)]
+    pub retracted_at: Option<Timestamp>,
+    /// The rated agent's public reply
+    #[serde(default)]
+    pub reply: Option<RatingReply>,
+    /// Agents who flagged the feedback as abusive or off-topic
+    #[serde(default)]
+    pub feedback_flags: Vec<Addr>,
+    /// Karma the rating earned the rated agent, taken back if it is amended
+    /// or retracted
+    #[serde(default)]
+    pub karma_awarded: Uint128,
+    /// Karma the rating cost the rated agent, given back if it is amended
+    /// or retracted
+    #[serde(default)]
+    pub karma_penalized: Uint128,
 }
 
 /// Karma score with historical tracking
@@ -39,12 +69,126 @@ pub struct KarmaScore {
     pub last_updated: Timestamp,
     /// Total number of ratings received
     pub total_ratings: u64,
-    /// Average rating score (stored as string to avoid floating point)
-    pub average_rating: String,
+    /// Average rating score (exact fixed-point decimal)
+    pub average_rating: Decimal,
     /// Interaction count at last calculation
     pub interaction_count: u64,
 }
 
+/// Running rating aggregates for an agent, maintained on every rating so
+/// karma can be calculated without rescanning the rating history
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct RatingAggregates {
+    /// Number of ratings per score (index 0 holds score 1)
+    pub histogram: [u64; 10],
+    /// Recency weight per score, decayed up to `weights_updated_at`
+    pub recency_weights: [Decimal; 10],
+    /// Time the recency weights were last decayed to
+    pub weights_updated_at: Timestamp,
+    /// Ratings per day (days since epoch) inside the recent activity window
+    pub recent_days: Vec<(u64, u64)>,
+    /// Number of distinct raters
+    pub unique_raters: u64,
+    /// Ratings from raters with high karma when they rated
+    pub high_karma_raters: u64,
+    /// Ratings from raters with low karma when they rated
+    pub low_karma_raters: u64,
+    /// Exponential moving average of scores in submission order
+    pub moving_average: Decimal,
+    /// Timestamp of the most recent rating
+    pub last_rating_at: Timestamp,
+}
+
+/// Progress of the rater trust computation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TrustEpoch {
+    /// Number of completed epochs
+    pub epoch: u64,
+    /// Last node processed in the epoch in progress
+    pub cursor: Option<String>,
+    /// Nodes in the rating graph when the epoch in progress started
+    pub node_count: u64,
+    /// Total trust of the latest completed epoch
+    pub mass: Decimal,
+    /// Trust handed out so far in the epoch in progress
+    pub next_mass: Decimal,
+    /// Node whose edges are being visited, when its edges did not fit in
+    /// one call
+    #[serde(default)]
+    pub node_in_progress: Option<TrustNodeProgress>,
+}
+
+impl Default for TrustEpoch {
+    fn default() -> Self {
+        Self {
+            epoch: 0,
+            cursor: None,
+            node_count: 0,
+            mass: Decimal::one(),
+            next_mass: Decimal::zero(),
+            node_in_progress: None,
+        }
+    }
+}
+
+/// Partial sums of a node whose edges are visited over several calls
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TrustNodeProgress {
+    pub node: String,
+    /// Last rater whose edge was visited
+    pub edge_cursor: Option<String>,
+    /// Trust flowing in from the raters visited so far
+    pub inflow: Decimal,
+    /// Ratings from the raters visited so far, weighted by rater trust
+    pub weighted_ratings: Decimal,
+    /// Ratings from the raters visited so far
+    pub ratings: u64,
+}
+
+/// Net satisfaction along each rating graph edge: the sum of `score - 5`
+/// over the rater's ratings of the agent
+/// Key: (rated_address, rater_address), Value: net satisfaction
+pub const TRUST_EDGES: Map<(&str, &str), i64> = Map::new("trust_edges");
+
+/// Positive satisfaction each rater has handed out, which normalises its edges
+/// Key: rater_address, Value: sum of positive edge satisfaction
+pub const TRUST_OUT_TOTALS: Map<&str, u64> = Map::new("trust_out_totals");
+
+/// Agents taking part in the rating graph, as raters or rated agents
+/// Key: agent_address, Value: empty
+pub const TRUST_NODES: Map<&str, ()> = Map::new("trust_nodes");
+
+/// Number of agents in the rating graph
+pub const TRUST_NODE_COUNT: Item<u64> = Item::new("trust_node_count");
+
+/// Trust scores, double-buffered by epoch parity: epoch `n` reads buffer
+/// `n % 2` and writes buffer `(n + 1) % 2`
+/// Key: (buffer, agent_address), Value: trust score
+pub const TRUST_SCORES: Map<(u8, &str), Decimal> = Map::new("trust_scores");
+
+/// Credibility of the ratings an agent received (0-1), from the latest epoch
+/// Key: agent_address, Value: credibility
+pub const TRUST_CREDIBILITY: Map<&str, Decimal> = Map::new("trust_credibility");
+
+/// Trust computation progress
+pub const TRUST_EPOCH: Item<TrustEpoch> = Item::new("trust_epoch");
+
+/// Running rating aggregates per agent and interaction type
+/// Key: (agent_address, interaction_type), Value: RatingAggregates
+pub const CATEGORY_AGGREGATES: Map<(&str, &str), RatingAggregates> = Map::new("category_aggregates");
+
+/// Number of ratings each rater gave an agent within an interaction type
+/// Key: (rated_address, interaction_type, rater_address), Value: rating count
+pub const CATEGORY_RATER_COUNTS: Map<(&str, &str, &str), u64> = Map::new("category_rater_counts");
+
+/// Agents whose karma is frozen because the agent registry deactivated them
+/// Key: agent_address, Value: time the karma was frozen
+pub const FROZEN_AGENTS: Map<&str, Timestamp> = Map::new("frozen_agents");
+
+/// Karma per agent and interaction type
+/// Key: (agent_address, interaction_type), Value: KarmaScore
+pub const CATEGORY_KARMA: Map<(&str, &str), KarmaScore> = Map::new("category_karma");
+
 /// Rating duplicate prevention tracking
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct RatingTracker {
@@ -52,8 +196,19 @@ pub struct RatingTracker {
     pub interaction_hash: String,
     /// Rater address
     pub rater: Addr,
-    /// Timestamp when rating was submitted
+    /// Timestamp when the latest rating was submitted
     pub submitted_at: Timestamp,
+    /// Agents rated for this interaction; empty for trackers recorded before
+    /// these were kept, which stand for a single rating
+    #[serde(default)]
+    pub rated_agents: Vec<Addr>,
+}
+
+impl RatingTracker {
+    /// Number of ratings the rater submitted for the interaction
+    pub fn rating_count(&self) -> usize {
+        std::cmp::max(self.rated_agents.len(), 1)
+    }
 }
 
 /// Indexes for ratings
@@ -64,11 +219,21 @@ pub struct RatingIndexes<'a> {
     pub rater: MultiIndex<'a, String, StoredRating, String>,
     /// Index by timestamp for chronological queries
     pub timestamp: MultiIndex<'a, u64, StoredRating, String>,
+    /// Index by rater, then timestamp, for a rater's recent ratings
+    pub rater_timestamp: MultiIndex<'a, (String, u64), StoredRating, String>,
+    /// Index by rated agent, then timestamp, for an agent's recent ratings
+    pub rated_timestamp: MultiIndex<'a, (String, u64), StoredRating, String>,
 }
 
 impl<'a> IndexList<StoredRating> for RatingIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<StoredRating>> + '_> {
-        let v: Vec<&dyn Index<StoredRating>> = vec![&self.rated_agent, &self.rater, &self.timestamp];
+        let v: Vec<&dyn Index<StoredRating>> = vec![
+            &self.rated_agent,
+            &self.rater,
+            &self.timestamp,
+            &self.rater_timestamp,
+            &self.rated_timestamp,
+        ];
         Box::new(v.into_iter())
     }
 }
@@ -80,9 +245,27 @@ pub const CONFIG: Item<Config> = Item::new("config");
 /// Key: agent address (String), Value: KarmaScore
 pub const KARMA_SCORES: Map<&str, KarmaScore> = Map::new("karma_scores");
 
-/// Historical karma calculations for tracking
+/// Net karma moved directly by fees, rewards, penalties, stakes and refunds,
+/// which recalculation adds to the score derived from ratings
+/// Key: agent address (String), Value: net movement
+pub const KARMA_ADJUSTMENTS: Map<&str, Int128> = Map::new("karma_adjustments");
+
+/// Historical karma calculations for tracking, in time order; the sequence
+/// number keeps calculations within one block apart
+/// Key: (agent_address, timestamp, sequence), Value: KarmaCalculation
+pub const KARMA_HISTORY: Map<(&str, u64, u64), KarmaCalculation> = Map::new("karma_history_series");
+
+/// Sequence number of the last recorded karma calculation
+pub const KARMA_HISTORY_SEQUENCE: Item<u64> = Item::new("karma_history_sequence");
+
+/// Daily summaries of karma history compacted by the retention policy
+/// Key: (agent_address, day start timestamp), Value: KarmaHistoryBucket
+pub const KARMA_HISTORY_BUCKETS: Map<(&str, u64), KarmaHistoryBucket> = Map::new("karma_history_buckets");
+
+/// History recorded before calculations carried a sequence number, moved
+/// into `KARMA_HISTORY` by `migrate` and `MigrateKarmaHistory`
 /// Key: (agent_address, timestamp), Value: KarmaCalculation
-pub const KARMA_HISTORY: Map<(&str, u64), KarmaCalculation> = Map::new("karma_history");
+pub const LEGACY_KARMA_HISTORY: Map<(&str, u64), KarmaCalculation> = Map::new("karma_history");
 
 /// Ratings storage with indexes
 /// Key: rating ID (String), Value: StoredRating
@@ -103,45 +286,225 @@ pub fn ratings<'a>() -> IndexedMap<'a, &'a str, StoredRating, RatingIndexes<'a>>
             "ratings",
             "ratings__timestamp",
         ),
+        rater_timestamp: MultiIndex::new(
+            |_pk: &[u8], r: &StoredRating| (r.rating.rater_address.to_string(), r.rating.timestamp.seconds()),
+            "ratings",
+            "ratings__rater_timestamp",
+        ),
+        rated_timestamp: MultiIndex::new(
+            |_pk: &[u8], r: &StoredRating| (r.rating.rated_address.to_string(), r.rating.timestamp.seconds()),
+            "ratings",
+            "ratings__rated_timestamp",
+        ),
     };
     IndexedMap::new("ratings", indexes)
 }
 
+/// Last rating re-saved by `BackfillRatingIndex`, so that ratings stored
+/// before the (rater, timestamp) and (rated agent, timestamp) indexes existed
+/// get indexed
+pub const RATING_INDEX_BACKFILL: Item<String> = Item::new("rating_index_backfill");
+
+/// Retracted ratings, moved out of the rating index with their revisions
+/// Key: rating ID (String), Value: StoredRating
+pub const RETRACTED_RATINGS: Map<&str, StoredRating> = Map::new("retracted_ratings");
+
+/// Running rating aggregates per rated agent
+/// Key: agent_address, Value: RatingAggregates
+pub const RATING_AGGREGATES: Map<&str, RatingAggregates> = Map::new("rating_aggregates");
+
+/// Aggregates of agents rated before aggregates existed, built up by
+/// `MigrateRatingAggregates` until every rating is folded in
+/// Key: agent_address, Value: RatingAggregates
+pub const MIGRATING_AGGREGATES: Map<&str, RatingAggregates> = Map::new("migrating_aggregates");
+
+/// Last rating folded by `MigrateRatingAggregates`, by (timestamp, rating ID)
+pub const AGGREGATE_MIGRATION: Item<(u64, String)> = Item::new("aggregate_migration");
+
+/// Number of ratings each rater gave an agent, for unique-rater counting
+/// Key: (rated_address, rater_address), Value: rating count
+pub const RATER_COUNTS: Map<(&str, &str), u64> = Map::new("rater_counts");
+
 /// Rating duplicate prevention tracking
 /// Key: (interaction_hash, rater_address), Value: RatingTracker
 pub const RATING_TRACKERS: Map<(&str, &str), RatingTracker> = Map::new("rating_trackers");
 
-/// Leaderboard storage for efficient queries
-/// Key: karma score (u128), Value: agent address (String)
-pub const LEADERBOARD: Map<u128, String> = Map::new("leaderboard");
+/// Leaderboard index, ascending key order is descending karma with ties
+/// broken by address
+///
+/// Agents are listed under their stored karma, which is only decayed when
+/// recalculated: decayed scores would need reordering whenever time passes.
+/// Key: (u128::MAX - karma score, agent address), Value: empty
+pub const LEADERBOARD: Map<(u128, &str), ()> = Map::new("leaderboard_index");
+
+/// Score each agent is currently listed under in the leaderboard
+/// Key: agent address, Value: karma score
+pub const LEADERBOARD_SCORES: Map<&str, u128> = Map::new("leaderboard_scores");
+
+/// Number of agents on the leaderboard
+pub const LEADERBOARD_SIZE: Item<u64> = Item::new("leaderboard_size");
+
+/// Leaderboard kept before ties were indexed, which listed one agent per
+/// score; cleared by `RebuildLeaderboard`
+/// Key: karma score, Value: agent address
+pub const LEGACY_LEADERBOARD: Map<u128, String> = Map::new("leaderboard");
+
+/// Last agent listed by `RebuildLeaderboard`
+pub const LEADERBOARD_REBUILD: Item<String> = Item::new("leaderboard_rebuild");
+
+/// Per interaction type leaderboard index, ordered like `LEADERBOARD`
+/// Key: (interaction_type, u128::MAX - karma score, agent address), Value: empty
+pub const CATEGORY_LEADERBOARD: Map<(&str, u128, &str), ()> = Map::new("category_leaderboard_index");
+
+/// Score each agent is currently listed under in a category leaderboard
+/// Key: (interaction_type, agent address), Value: karma score
+pub const CATEGORY_LEADERBOARD_SCORES: Map<(&str, &str), u128> = Map::new("category_leaderboard_scores");
 
 /// Counter for rating IDs
 pub const RATING_COUNTER: Item<u64> = Item::new("rating_counter");
 
+/// Latest parsed oracle reading of one data type for an agent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OracleReading {
+    /// Provider that submitted the data
+    pub provider: Addr,
+    /// Parsed payload
+    pub payload: OraclePayload,
+    /// Submission timestamp reported by the oracle
+    pub submitted_at: Timestamp,
+}
+
 /// Oracle data integration storage
+/// Key: (agent_address, data_type), Value: latest reading
+pub const ORACLE_DATA: Map<(&str, &str), OracleReading> = Map::new("oracle_readings");
+
+/// Oracle data stored as an opaque `"{timestamp}:{len}"` string before
+/// payloads were parsed, cleared by `migrate`
 /// Key: (agent_address, data_type), Value: oracle data hash
-pub const ORACLE_DATA: Map<(&str, &str), String> = Map::new("oracle_data");
+pub const LEGACY_ORACLE_DATA: Map<(&str, &str), String> = Map::new("oracle_data");
+
+/// Running totals of an agent's karma ledger
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct LedgerTotals {
+    /// Number of entries, and sequence of the next one
+    pub entries: u64,
+    /// Sum of all credits
+    pub credited: Uint128,
+    /// Sum of all debits
+    pub debited: Uint128,
+}
+
+/// Itemised karma movements, in booking order
+/// Key: (agent_address, sequence), Value: KarmaLedgerEntry
+pub const KARMA_LEDGER: Map<(&str, u64), KarmaLedgerEntry> = Map::new("karma_ledger");
+
+/// Ledger totals per agent
+/// Key: agent_address, Value: LedgerTotals
+pub const KARMA_LEDGER_TOTALS: Map<&str, LedgerTotals> = Map::new("karma_ledger_totals");
 
-/// Karma earning/spending tracking
+/// Earned/spent totals kept before karma movements were itemised, replaced
+/// by opening ledger entries in `migrate` and `MigrateKarmaLedgers`
 /// Key: agent_address, Value: (earned, spent)
-pub const KARMA_BALANCE: Map<&str, (Uint128, Uint128)> = Map::new("karma_balance");
+pub const LEGACY_KARMA_BALANCE: Map<&str, (Uint128, Uint128)> = Map::new("karma_balance");
+
+/// Last agent whose ledger `MigrateKarmaLedgers` reached, kept until every
+/// agent holding karma has a ledger
+pub const LEDGER_MIGRATION: Item<String> = Item::new("ledger_migration");
 
 /// Compliance violations storage
 /// Key: violation_id, Value: ComplianceViolation
 pub const COMPLIANCE_VIOLATIONS: Map<&str, crate::compliance::ComplianceViolation> = Map::new("compliance_violations");
 
+/// Violations recorded against each agent
+/// Key: (agent_address, violation_id), Value: violation time
+pub const AGENT_VIOLATIONS: Map<(&str, &str), Timestamp> = Map::new("agent_violations");
+
+/// Last violation filed under its agent by `MigrateViolationIndex`, so that
+/// violations recorded before `AGENT_VIOLATIONS` existed get filed
+pub const VIOLATION_INDEX_MIGRATION: Item<String> = Item::new("violation_index_migration");
+
+/// Last time an enforcement policy acted on a violation type for an agent
+/// Key: (agent_address, violation_type), Value: enforcement time
+pub const ENFORCEMENT_COOLDOWNS: Map<(&str, &str), Timestamp> = Map::new("enforcement_cooldowns");
+
+/// Agents barred from rating by an enforcement policy
+/// Key: agent_address, Value: end of the suspension
+pub const RATING_SUSPENSIONS: Map<&str, Timestamp> = Map::new("rating_suspensions");
+
 /// Dispute cases storage
 /// Key: case_id, Value: DisputeCase
 pub const DISPUTE_CASES: Map<&str, crate::compliance::DisputeCase> = Map::new("dispute_cases");
 
-/// Rate limiting trackers
-/// Key: agent_address, Value: RateLimitTracker
-pub const RATE_LIMIT_TRACKERS: Map<&str, crate::compliance::RateLimitTracker> = Map::new("rate_limit_trackers");
+/// Disputes filed against each violation
+/// Key: (violation_id, case_id), Value: ()
+pub const VIOLATION_DISPUTES: Map<(&str, &str), ()> = Map::new("violation_disputes");
+
+/// Last dispute filed under its violation by `MigrateDisputeIndex`, so that
+/// disputes filed before `VIOLATION_DISPUTES` existed get filed
+pub const DISPUTE_INDEX_MIGRATION: Item<String> = Item::new("dispute_index_migration");
+
+/// Counter for dispute case IDs
+pub const DISPUTE_COUNTER: Item<u64> = Item::new("dispute_counter");
+
+/// Counter for violation IDs
+pub const VIOLATION_COUNTER: Item<u64> = Item::new("violation_counter");
+
+/// Stake forfeited by lost disputes, paid out as rewards for won ones
+pub const FORFEITED_STAKE_POOL: Item<Uint128> = Item::new("forfeited_stake_pool");
+
+/// Juror panel deciding a dispute, with its voting deadlines
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Arbitration {
+    /// Jurors drawn for the dispute
+    pub jurors: Vec<Addr>,
+    /// End of the commit phase
+    pub commit_deadline: Timestamp,
+    /// End of the reveal phase
+    pub reveal_deadline: Timestamp,
+    /// Resolution the jurors reached, once finalized
+    pub resolution: Option<crate::compliance::DisputeResolution>,
+}
+
+/// A juror's sealed vote, and the vote itself once revealed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JurorVote {
+    /// Hex SHA-256 commitment to the vote
+    pub commitment: String,
+    pub revealed: Option<crate::compliance::DisputeResolution>,
+}
+
+/// Disputes under juror arbitration
+/// Key: case_id, Value: Arbitration
+pub const ARBITRATIONS: Map<&str, Arbitration> = Map::new("arbitrations");
+
+/// Juror votes per dispute
+/// Key: (case_id, juror_address), Value: JurorVote
+pub const JUROR_VOTES: Map<(&str, &str), JurorVote> = Map::new("juror_votes");
+
+/// Use of a sliding-window quota: one action, or karma earned, at a time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WindowEvent {
+    pub at: Timestamp,
+    /// Actions performed, or karma earned, at that time
+    pub amount: Uint128,
+}
+
+/// Quota use within the current window, oldest first; karma earned is kept
+/// under `rate_limit::EARNING_ACTION`
+/// Key: (agent_address, action), Value: Vec<WindowEvent>
+pub const ACTION_WINDOWS: Map<(&str, &str), Vec<WindowEvent>> = Map::new("action_windows");
+
+/// Result of an abuse detector the last time it ran on an agent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DetectorRun {
+    pub detected_at: Timestamp,
+    pub result: agent_karma_contracts::messages::karma_core::AbuseDetectionResult,
+}
 
-/// Abuse pattern detection storage
-/// Key: (agent_address, pattern_type), Value: detection_data
-pub const ABUSE_PATTERNS: Map<(&str, &str), String> = Map::new("abuse_patterns");
+/// Latest abuse detection results
+/// Key: (agent_address, detector), Value: DetectorRun
+pub const ABUSE_PATTERNS: Map<(&str, &str), DetectorRun> = Map::new("abuse_patterns");
 
 /// Karma penalties tracking
-/// Key: penalty_id, Value: penalty_amount
+/// Key: violation_id, Value: penalty_amount
 pub const KARMA_PENALTIES: Map<&str, Uint128> = Map::new("karma_penalties");
\ No newline at end of file
@@ -0,0 +1,269 @@
+use cosmwasm_std::{Api, Decimal, Order, StdResult, Storage};
+use cw_storage_plus::Bound;
+use crate::error::ContractError;
+use crate::state::{
+    TrustEpoch, TrustNodeProgress, RATER_COUNTS, TRUST_CREDIBILITY, TRUST_EDGES, TRUST_EPOCH, TRUST_NODES,
+    TRUST_NODE_COUNT, TRUST_OUT_TOTALS, TRUST_SCORES,
+};
+use agent_karma_contracts::types::TrustConfig;
+
+/// Rating score that neither adds nor removes satisfaction
+const NEUTRAL_SCORE: i64 = 5;
+
+/// Outcome of a single `advance_trust_epoch` call
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrustProgress {
+    /// Epoch the processed nodes belong to
+    pub epoch: u64,
+    pub nodes_processed: u64,
+    /// Whether this call completed the epoch
+    pub completed: bool,
+}
+
+/// Satisfaction a single rating adds to its edge
+pub fn rating_satisfaction(score: u8) -> i64 {
+    score as i64 - NEUTRAL_SCORE
+}
+
+/// Add satisfaction to the rating graph edge from `rater` to `rated`
+pub fn record_trust_edge(
+    storage: &mut dyn Storage,
+    rated: &str,
+    rater: &str,
+    satisfaction: i64,
+) -> StdResult<()> {
+    add_node(storage, rated)?;
+    add_node(storage, rater)?;
+
+    let old = TRUST_EDGES.may_load(storage, (rated, rater))?.unwrap_or(0);
+    let new = old + satisfaction;
+    TRUST_EDGES.save(storage, (rated, rater), &new)?;
+
+    // Only positive satisfaction passes trust on
+    let out_total = TRUST_OUT_TOTALS.may_load(storage, rater)?.unwrap_or(0) as i64;
+    let out_total = out_total + new.max(0) - old.max(0);
+    TRUST_OUT_TOTALS.save(storage, rater, &(out_total as u64))?;
+
+    Ok(())
+}
+
+fn add_node(storage: &mut dyn Storage, agent: &str) -> StdResult<()> {
+    if !TRUST_NODES.has(storage, agent) {
+        TRUST_NODES.save(storage, agent, &())?;
+        let count = TRUST_NODE_COUNT.may_load(storage)?.unwrap_or(0);
+        TRUST_NODE_COUNT.save(storage, &(count + 1))?;
+    }
+    Ok(())
+}
+
+/// Advance the trust computation, spending at most `max_steps` node and
+/// edge visits
+///
+/// An epoch is one power iteration `t' = (1 - a) C^T t + a p` over every node
+/// of the rating graph, where `C` holds each rater's normalised positive
+/// satisfaction, `p` the pre-trust distribution and `a` the damping. While a
+/// node's next trust is computed, the credibility of the ratings it received
+/// is refreshed from its raters' current trust. A node with more edges than
+/// the budget allows is visited over several calls, resuming after the last
+/// edge visited.
+pub fn advance_trust_epoch(
+    storage: &mut dyn Storage,
+    config: &TrustConfig,
+    max_steps: u64,
+) -> StdResult<TrustProgress> {
+    let mut state = TRUST_EPOCH.may_load(storage)?.unwrap_or_default();
+    if state.cursor.is_none() && state.node_in_progress.is_none() {
+        state.node_count = TRUST_NODE_COUNT.may_load(storage)?.unwrap_or(0);
+
+        // Nothing to iterate over until the first rating is recorded
+        if TRUST_NODES.is_empty(storage) {
+            return Ok(TrustProgress {
+                epoch: state.epoch,
+                nodes_processed: 0,
+                completed: false,
+            });
+        }
+    }
+
+    let mut budget = max_steps.max(1);
+    let mut nodes_processed = 0;
+    let mut completed = false;
+    loop {
+        let mut progress = match state.node_in_progress.take() {
+            Some(progress) => progress,
+            None => {
+                // A node visit only starts while budget remains
+                let Some(node) = next_node(storage, &state)? else {
+                    completed = true;
+                    break;
+                };
+                if budget == 0 {
+                    break;
+                }
+                budget -= 1;
+                TrustNodeProgress {
+                    node,
+                    edge_cursor: None,
+                    inflow: Decimal::zero(),
+                    weighted_ratings: Decimal::zero(),
+                    ratings: 0,
+                }
+            }
+        };
+
+        let visited_all = visit_edges(storage, config, &state, &mut progress, &mut budget)?;
+        if !visited_all {
+            state.node_in_progress = Some(progress);
+            break;
+        }
+
+        let trust = finish_node(storage, config, &state, &progress)?;
+        state.next_mass += trust;
+        state.cursor = Some(progress.node);
+        nodes_processed += 1;
+    }
+
+    let epoch = state.epoch;
+    if completed {
+        state = TrustEpoch {
+            epoch: state.epoch + 1,
+            mass: state.next_mass,
+            ..TrustEpoch::default()
+        };
+    }
+    TRUST_EPOCH.save(storage, &state)?;
+
+    Ok(TrustProgress {
+        epoch,
+        nodes_processed,
+        completed,
+    })
+}
+
+/// Node after the epoch's cursor, if any is left
+fn next_node(storage: &dyn Storage, state: &TrustEpoch) -> StdResult<Option<String>> {
+    let start = state.cursor.as_deref().map(Bound::exclusive);
+    TRUST_NODES
+        .keys(storage, start, None, Order::Ascending)
+        .next()
+        .transpose()
+}
+
+/// Visit a node's incoming edges after its edge cursor, one budget step per
+/// edge, and return whether every edge has been visited
+fn visit_edges(
+    storage: &dyn Storage,
+    config: &TrustConfig,
+    state: &TrustEpoch,
+    progress: &mut TrustNodeProgress,
+    budget: &mut u64,
+) -> StdResult<bool> {
+    let current = (state.epoch % 2) as u8;
+    let node = progress.node.as_str();
+
+    let start = progress.edge_cursor.as_deref().map(Bound::exclusive);
+    let edges = TRUST_EDGES
+        .prefix(node)
+        .range(storage, start, None, Order::Ascending)
+        .take(*budget as usize + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+    let visited_all = edges.len() as u64 <= *budget;
+
+    for (rater, satisfaction) in edges.into_iter().take(*budget as usize) {
+        let rater_trust = trust_score(storage, config, current, &rater)?;
+
+        if satisfaction > 0 {
+            let out_total = TRUST_OUT_TOTALS.load(storage, &rater)?;
+            progress.inflow += rater_trust * Decimal::from_ratio(satisfaction as u64, out_total);
+        }
+
+        // A rater at or above the average trust counts fully
+        let count = RATER_COUNTS.may_load(storage, (node, rater.as_str()))?.unwrap_or(0);
+        progress.weighted_ratings += rater_weight(rater_trust, state) * Decimal::from_ratio(count, 1u8);
+        progress.ratings += count;
+
+        progress.edge_cursor = Some(rater);
+        *budget -= 1;
+    }
+
+    Ok(visited_all)
+}
+
+/// Store a fully visited node's trust for the next epoch and the credibility
+/// of its ratings, returning the trust
+fn finish_node(
+    storage: &mut dyn Storage,
+    config: &TrustConfig,
+    state: &TrustEpoch,
+    progress: &TrustNodeProgress,
+) -> StdResult<Decimal> {
+    let next = ((state.epoch + 1) % 2) as u8;
+    let node = progress.node.as_str();
+
+    let trust = (Decimal::one() - config.damping) * progress.inflow
+        + config.damping * pre_trust(config, node);
+    TRUST_SCORES.save(storage, (next, node), &trust)?;
+
+    if progress.ratings > 0 {
+        let credibility = progress.weighted_ratings / Decimal::from_ratio(progress.ratings, 1u8);
+        TRUST_CREDIBILITY.save(storage, node, &credibility)?;
+    }
+
+    Ok(trust)
+}
+
+/// Trust of an agent in the given buffer, defaulting to its pre-trust
+fn trust_score(storage: &dyn Storage, config: &TrustConfig, buffer: u8, agent: &str) -> StdResult<Decimal> {
+    Ok(TRUST_SCORES
+        .may_load(storage, (buffer, agent))?
+        .unwrap_or_else(|| pre_trust(config, agent)))
+}
+
+/// Share of the initial trust an agent holds
+fn pre_trust(config: &TrustConfig, agent: &str) -> Decimal {
+    if config.pre_trusted.iter().any(|trusted| trusted == agent) {
+        Decimal::from_ratio(1u8, config.pre_trusted.len() as u64)
+    } else {
+        Decimal::zero()
+    }
+}
+
+/// Weight of a rater's ratings: its trust relative to the average, capped at
+/// one
+///
+/// Without any trust to compare against, ratings count fully rather than
+/// not at all.
+fn rater_weight(trust: Decimal, state: &TrustEpoch) -> Decimal {
+    if state.mass.is_zero() {
+        return Decimal::one();
+    }
+    let relative = trust * Decimal::from_ratio(state.node_count, 1u8) / state.mass;
+    std::cmp::min(relative, Decimal::one())
+}
+
+/// Converged trust of an agent as of the latest completed epoch
+pub fn query_trust(storage: &dyn Storage, config: &TrustConfig, agent: &str) -> StdResult<Decimal> {
+    let state = TRUST_EPOCH.may_load(storage)?.unwrap_or_default();
+    trust_score(storage, config, (state.epoch % 2) as u8, agent)
+}
+
+/// Validate trust parameters before they are stored
+pub fn validate_trust_config(api: &dyn Api, config: &TrustConfig) -> Result<(), ContractError> {
+    if config.damping.is_zero() || config.damping >= Decimal::one() {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "Trust damping must be between 0 and 1".to_string(),
+        });
+    }
+
+    if config.pre_trusted.is_empty() {
+        return Err(ContractError::InvalidKarmaConfig {
+            reason: "At least one agent must be pre-trusted".to_string(),
+        });
+    }
+
+    for agent in config.pre_trusted.iter() {
+        api.addr_validate(agent)?;
+    }
+
+    Ok(())
+}
@@ -3,7 +3,7 @@
 //! This module defines all the message types used for contract instantiation,
 //! execution, and queries across the Agent-Karma ecosystem.
 
-use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use crate::types::*;
@@ -88,6 +88,7 @@ pub mod karma_core {
     /// Execute messages for Karma Core contract
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     #[serde(rename_all = "snake_case")]
+    #[allow(clippy::large_enum_variant)] // decoded once per call; boxing the config buys nothing
     pub enum ExecuteMsg {
         /// Submit a rating for another agent
         SubmitRating {
@@ -96,6 +97,26 @@ pub mod karma_core {
             feedback: Option<String>,
             interaction_hash: String,
         },
+        /// Change the score or feedback of one's own rating within the
+        /// rating grace period
+        AmendRating {
+            rating_id: String,
+            score: u8,
+            feedback: Option<String>,
+        },
+        /// Withdraw one's own rating within the rating grace period, refunding its fee
+        RetractRating {
+            rating_id: String,
+        },
+        /// Post the rated agent's public reply to a rating (one per rating)
+        RespondToRating {
+            rating_id: String,
+            reply: String,
+        },
+        /// Flag a rating's feedback as abusive or off-topic
+        FlagFeedback {
+            rating_id: String,
+        },
         /// Recalculate karma for an agent (can be called by anyone)
         RecalculateKarma {
             agent_address: String,
@@ -120,49 +141,203 @@ pub mod karma_core {
             severity: u8,
             evidence: String,
         },
+        /// Return the share of an agent's penalties recovered in good standing
+        /// (can be called by anyone)
+        RehabilitateAgent {
+            agent_address: String,
+        },
         /// Create dispute for false positive detection
         CreateDispute {
             violation_id: String,
             stake_amount: Uint128,
             evidence: String,
         },
-        /// Resolve dispute case (admin only)
+        /// Resolve dispute case (admin only, for disputes no juror panel
+        /// could be drawn for)
         ResolveDispute {
             case_id: String,
             resolution: String, // "confirmed", "overturned", "partial"
         },
+        /// Draw the juror panel of a dispute in the block scheduled when it
+        /// was filed (can be called by anyone)
+        DrawJurors {
+            case_id: String,
+        },
+        /// Commit a sealed juror vote: hex SHA-256 of
+        /// "{case_id}:{juror}:{resolution}:{salt}"
+        CommitJurorVote {
+            case_id: String,
+            commitment: String,
+        },
+        /// Reveal a committed juror vote
+        RevealJurorVote {
+            case_id: String,
+            resolution: String, // "confirmed", "overturned", "partial"
+            salt: String,
+        },
+        /// Resolve a dispute with its jurors' votes once voting is over
+        /// (can be called by anyone)
+        FinalizeArbitration {
+            case_id: String,
+        },
+        /// Advance the rater trust computation by up to `limit` node and edge
+        /// visits (can be called by anyone)
+        AdvanceTrustEpoch {
+            limit: Option<u32>,
+        },
+        /// Index up to `limit` ratings stored before the (rater, timestamp)
+        /// and (rated agent, timestamp) indexes existed (can be called by
+        /// anyone)
+        BackfillRatingIndex {
+            limit: Option<u32>,
+        },
+        /// List up to `limit` more agents on the leaderboard, rebuilding it
+        /// from karma scores after an upgrade from the score-keyed
+        /// leaderboard (can be called by anyone)
+        RebuildLeaderboard {
+            limit: Option<u32>,
+        },
+        /// Move up to `limit` more karma history entries recorded before
+        /// calculations were sequenced, finishing what `migrate` left over
+        /// (can be called by anyone)
+        MigrateKarmaHistory {
+            limit: Option<u32>,
+        },
+        /// Open the karma ledgers of up to `limit` more agents holding karma
+        /// from before movements were itemised, finishing what `migrate` left
+        /// over (can be called by anyone)
+        MigrateKarmaLedgers {
+            limit: Option<u32>,
+        },
+        /// File up to `limit` more violations recorded before they were kept
+        /// per agent under their agent, finishing what `migrate` left over
+        /// (can be called by anyone)
+        MigrateViolationIndex {
+            limit: Option<u32>,
+        },
+        /// File up to `limit` more disputes filed before they were kept per
+        /// violation under their violation, finishing what `migrate` left
+        /// over (can be called by anyone)
+        MigrateDisputeIndex {
+            limit: Option<u32>,
+        },
+        /// Aggregate up to `limit` more ratings given before rating
+        /// aggregates existed, finishing what `migrate` left over (can be
+        /// called by anyone)
+        MigrateRatingAggregates {
+            limit: Option<u32>,
+        },
+        /// Set the agent-registry and interaction-logger contracts karma-core queries (admin only)
+        UpdateContractAddresses {
+            agent_registry: Option<String>,
+            interaction_logger: Option<String>,
+        },
+        /// Freeze or thaw an agent's karma to match its agent-registry status
+        /// (can be called by anyone)
+        SyncAgentStatus {
+            agent_address: String,
+        },
     }
 
     /// Query messages for Karma Core contract
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     #[serde(rename_all = "snake_case")]
     pub enum QueryMsg {
-        /// Get current karma score
+        /// Get current karma score, with time decay applied up to the current block
         GetKarmaScore {
             agent_address: String,
         },
+        /// Get the karma score projected to a given time, assuming no new activity
+        GetKarmaScoreAt {
+            agent_address: String,
+            time: Timestamp,
+        },
         /// Get detailed karma calculation
         GetKarmaCalculation {
             agent_address: String,
         },
-        /// Get karma history
+        /// Explain the current karma score component by component
+        GetKarmaBreakdown {
+            agent_address: String,
+        },
+        /// Get karma history between two times (inclusive), oldest first
         GetKarmaHistory {
             agent_address: String,
+            from: Option<Timestamp>,
+            to: Option<Timestamp>,
+            start_after: Option<KarmaHistoryCursor>,
+            limit: Option<u32>,
+        },
+        /// Get an agent's itemised karma movements, oldest first
+        GetKarmaLedger {
+            agent_address: String,
+            /// Sequence of the last entry already seen
+            start_after: Option<u64>,
+            limit: Option<u32>,
+        },
+        /// Get karma history downsampled into daily or weekly buckets
+        GetKarmaHistoryBuckets {
+            agent_address: String,
+            interval: HistoryInterval,
+            from: Option<Timestamp>,
+            to: Option<Timestamp>,
+            /// Start of the last bucket of the previous page
             start_after: Option<Timestamp>,
             limit: Option<u32>,
         },
-        /// Get ratings for an agent
+        /// Get ratings for an agent; feedback flagged past the threshold is
+        /// left out unless `include_hidden_feedback` is set
         GetAgentRatings {
             agent_address: String,
             start_after: Option<String>,
             limit: Option<u32>,
+            include_hidden_feedback: Option<bool>,
         },
-        /// Get leaderboard (top karma scores)
+        /// Get a rating, amended or retracted, with its earlier versions
+        GetRatingRevisions {
+            rating_id: String,
+        },
+        /// Get leaderboard (top karma scores), paginated by the cursor of the previous page
+        ///
+        /// Agents are ranked by karma as of their last calculation, without
+        /// the inactivity decay `GetKarmaScore` applies at query time.
         GetLeaderboard {
+            start_after: Option<LeaderboardCursor>,
+            limit: Option<u32>,
+        },
+        /// Get the leaderboard restricted to agents of one framework
+        GetLeaderboardByFramework {
+            framework: String,
+            start_after: Option<LeaderboardCursor>,
             limit: Option<u32>,
         },
+        /// Get an agent's karma within each interaction type it was rated in
+        GetCategoryKarma {
+            agent_address: String,
+        },
+        /// Get the leaderboard of karma within one interaction type
+        GetCategoryLeaderboard {
+            interaction_type: String,
+            start_after: Option<LeaderboardCursor>,
+            limit: Option<u32>,
+        },
+        /// Get an agent's leaderboard rank, by undecayed karma like `GetLeaderboard`
+        GetAgentRank {
+            agent_address: String,
+        },
+        /// Get an agent's transitive trust and the credibility of its raters
+        GetTrustScore {
+            agent_address: String,
+        },
         /// Get karma configuration
         GetConfig {},
+        /// Dry-run a rating submission without changing any state
+        SimulateRating {
+            rater: String,
+            rated_agent: String,
+            score: u8,
+            interaction_hash: String,
+        },
         /// Get compliance violations for an agent
         GetComplianceViolations {
             agent_address: String,
@@ -175,6 +350,10 @@ pub mod karma_core {
             start_after: Option<String>,
             limit: Option<u32>,
         },
+        /// Get the juror panel and votes of a dispute
+        GetArbitration {
+            case_id: String,
+        },
         /// Get abuse detection results for an agent
         GetAbuseDetectionResults {
             agent_address: String,
@@ -184,6 +363,11 @@ pub mod karma_core {
             agent_address: String,
             action_type: String,
         },
+        /// Get an agent's violations, penalties, disputes, rate limits and
+        /// latest abuse detection results, with the risk tier they add up to
+        GetComplianceReport {
+            agent_address: String,
+        },
     }
 
     /// Response types for Karma Core queries
@@ -198,27 +382,145 @@ pub mod karma_core {
         pub calculation: KarmaCalculation,
     }
 
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct KarmaBreakdownResponse {
+        pub agent_address: Addr,
+        /// Score the components add up to
+        pub score: Uint128,
+        pub algorithm_version: KarmaAlgorithmVersion,
+        pub calculated_at: Timestamp,
+        pub components: Vec<KarmaComponent>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct CategoryKarma {
+        pub interaction_type: String,
+        /// Score with time decay applied up to the current block
+        pub score: Uint128,
+        pub rating_count: u64,
+        pub last_updated: Timestamp,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct CategoryKarmaResponse {
+        pub agent_address: Addr,
+        pub categories: Vec<CategoryKarma>,
+    }
+
+    /// Position of a calculation in an agent's karma history
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct KarmaHistoryCursor {
+        pub time: Timestamp,
+        pub sequence: u64,
+    }
+
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct KarmaHistoryResponse {
         pub history: Vec<KarmaCalculation>,
+        /// Cursor to pass as `start_after` for the next page, if any
+        pub next_cursor: Option<KarmaHistoryCursor>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct KarmaLedgerResponse {
+        pub entries: Vec<KarmaLedgerEntry>,
+        /// Entries in the whole ledger
+        pub entry_count: u64,
+        /// Credits across the whole ledger
+        pub total_credited: Uint128,
+        /// Debits across the whole ledger
+        pub total_debited: Uint128,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct KarmaHistoryBucketsResponse {
+        pub buckets: Vec<KarmaHistoryBucket>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct RatingsResponse {
         pub ratings: Vec<Rating>,
+        /// Replies to the listed ratings
+        pub replies: Vec<RatingReply>,
+        /// IDs of the listed ratings whose feedback was flagged past the threshold
+        pub hidden_feedback: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct RatingRevisionsResponse {
+        /// Latest version of the rating
+        pub rating: Rating,
+        /// Earlier versions, oldest first
+        pub revisions: Vec<RatingRevision>,
+        pub retracted_at: Option<Timestamp>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct LeaderboardEntry {
         pub agent_address: Addr,
+        /// Karma as of the agent's last calculation, before decay
         pub karma_score: Uint128,
         pub agent_name: String,
         pub framework: String,
     }
 
+    /// Position in a leaderboard: the score an agent is listed under and the
+    /// agent, which breaks ties
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct LeaderboardCursor {
+        pub karma_score: Uint128,
+        pub agent_address: String,
+    }
+
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct LeaderboardResponse {
         pub leaderboard: Vec<LeaderboardEntry>,
+        /// Cursor to pass as `start_after` for the next page, if any
+        pub next_cursor: Option<LeaderboardCursor>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct SimulateRatingResponse {
+        /// Whether the rating would be accepted
+        pub success: bool,
+        /// Reason the rating would be rejected
+        pub error: Option<String>,
+        /// Rated agent's karma before the rating
+        pub current_score: Uint128,
+        /// Rated agent's karma after the rating, if accepted
+        pub projected_score: Option<Uint128>,
+        /// Karma fee charged to the rater
+        pub fee: Uint128,
+        /// Karma the rated agent would earn
+        pub karma_earned: Uint128,
+        /// Karma the rated agent would be penalised
+        pub karma_penalty: Uint128,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct AgentRankResponse {
+        pub agent_address: Addr,
+        /// 1-based rank, `None` if the agent is not on the leaderboard or is
+        /// listed below the positions counted exactly
+        pub rank: Option<u64>,
+        /// Set for agents listed below the positions counted exactly: their
+        /// rank is greater than this
+        pub ranked_below: Option<u64>,
+        /// Karma the agent is ranked by, before decay
+        pub karma_score: Uint128,
+        /// Number of agents on the leaderboard
+        pub total_ranked: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct TrustScoreResponse {
+        pub agent_address: Addr,
+        /// Trust as of the latest completed epoch
+        pub trust: Decimal,
+        /// Credibility of the ratings the agent received (0-1), once an epoch reached it
+        pub credibility: Option<Decimal>,
+        /// Number of completed trust epochs
+        pub epoch: u64,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -236,6 +538,18 @@ pub mod karma_core {
         pub evidence: String,
         pub penalty_applied: Uint128,
         pub disputed: bool,
+        /// Part of the penalty given back after a dispute
+        pub penalty_refunded: Uint128,
+        /// Whether a dispute overturned the violation outright
+        pub overturned: bool,
+        /// Policy action that recorded the violation; `None` for manual penalties
+        pub enforcement: Option<EnforcementAction>,
+        /// Part of the penalty recovered in good standing
+        pub penalty_recovered: Uint128,
+        /// When the violation leaves the agent's active record
+        pub expires_at: Timestamp,
+        /// Whether the violation is neither expired nor overturned
+        pub active: bool,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -254,6 +568,8 @@ pub mod karma_core {
         pub created_at: Timestamp,
         pub resolved_at: Option<Timestamp>,
         pub resolution: Option<String>,
+        /// Block `DrawJurors` must be called in, while the draw is pending
+        pub juror_draw_height: Option<u64>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -261,6 +577,23 @@ pub mod karma_core {
         pub cases: Vec<DisputeCase>,
     }
 
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct JurorStatus {
+        pub juror: Addr,
+        pub committed: bool,
+        /// Revealed vote, if any
+        pub vote: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct ArbitrationResponse {
+        pub case_id: String,
+        pub jurors: Vec<JurorStatus>,
+        pub commit_deadline: Timestamp,
+        pub reveal_deadline: Timestamp,
+        pub resolution: Option<String>,
+    }
+
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct AbuseDetectionResult {
         pub is_suspicious: bool,
@@ -278,18 +611,78 @@ pub mod karma_core {
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct RateLimitStatus {
         pub agent_address: Addr,
+        /// Action type, or "earning" for the daily earning cap
         pub action_type: String,
-        pub current_count: u32,
-        pub limit: u32,
+        /// Actions performed, or karma earned, within the window
+        pub current_count: Uint128,
+        pub limit: Uint128,
+        /// Sliding window the limit applies over, ending now
         pub window_start: Timestamp,
         pub window_end: Timestamp,
-        pub remaining_actions: u32,
+        pub remaining_actions: Uint128,
+        /// When the oldest counted use leaves the window, freeing quota
+        pub resets_at: Option<Timestamp>,
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
     pub struct RateLimitStatusResponse {
         pub status: RateLimitStatus,
     }
+
+    /// How far partners should trust an agent's compliance record
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ComplianceRiskTier {
+        /// No active violation or suspicious detection
+        Low,
+        /// Active violations of low total severity
+        Elevated,
+        /// Active violations of total severity 10 or more, or a suspicious
+        /// latest detection
+        High,
+        /// Rating rights suspended, an escalated violation, or active
+        /// violations of total severity 20 or more
+        Critical,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct ViolationCount {
+        pub violation_type: String,
+        pub total: u32,
+        /// Violations neither expired nor overturned
+        pub active: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct DetectorReport {
+        /// Violation type the detector looks for
+        pub detector: String,
+        pub detected_at: Timestamp,
+        pub result: AbuseDetectionResult,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    pub struct ComplianceReportResponse {
+        pub agent_address: Addr,
+        pub risk_tier: ComplianceRiskTier,
+        /// Violations by type, for types the agent has any of
+        pub violations: Vec<ViolationCount>,
+        pub penalty_applied: Uint128,
+        /// Penalties given back after disputes
+        pub penalty_refunded: Uint128,
+        /// Penalties recovered in good standing
+        pub penalty_recovered: Uint128,
+        /// Penalties still held, net of refunds and recoveries
+        pub outstanding_penalty: Uint128,
+        /// Disputes of the agent's violations awaiting a resolution
+        pub open_disputes: u32,
+        pub resolved_disputes: u32,
+        /// Standing against every configured action limit and the earning cap
+        pub rate_limits: Vec<RateLimitStatus>,
+        pub rating_suspended_until: Option<Timestamp>,
+        /// Result of each abuse detector the last time it ran on the agent
+        pub latest_detections: Vec<DetectorReport>,
+    }
 }
 
 /// Interaction Logger Messages
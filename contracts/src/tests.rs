@@ -10,10 +10,13 @@ mod tests {
     use crate::errors::validation::*;
     use crate::events::{AgentRegistryEvents, InteractionLoggerEvents, KarmaCoreEvents};
     use crate::types::{
-        Agent, AgentMetadata, Interaction, InteractionMetadata, KarmaCalculation, KarmaConfig,
-        KarmaFactors, OracleData, Proposal, ProposalStatus, Rating, Vote,
+        Agent, AgentMetadata, ArbitrationConfig, EnforcementConfig, RateLimitConfig, RehabilitationConfig, Interaction, InteractionMetadata, KarmaAlgorithmConfig,
+        KarmaAlgorithmVersion, KarmaCalculation, KarmaConfig, KarmaFactors, OracleData, Proposal,
+        ProposalStatus, Rating, TrustConfig, Vote, DEFAULT_DECAY_HALF_LIFE,
+        DEFAULT_RATING_GRACE_PERIOD, DEFAULT_MIN_KARMA_FOR_FLAGGING,
+        DEFAULT_FEEDBACK_FLAG_THRESHOLD,
     };
-    use cosmwasm_std::{Addr, Timestamp, Uint128};
+    use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 
     /// Test agent metadata creation and validation
     #[test]
@@ -103,10 +106,10 @@ mod tests {
     #[test]
     fn test_karma_calculation() {
         let factors = KarmaFactors {
-            average_rating: "7.5".to_string(),
+            average_rating: Decimal::percent(750),
             rating_count: 10,
             interaction_frequency: Uint128::new(50),
-            time_decay: "0.95".to_string(),
+            time_decay: Decimal::percent(95),
             external_factors: Some(Uint128::new(20)),
         };
 
@@ -117,6 +120,7 @@ mod tests {
             factors,
             last_updated: Timestamp::from_seconds(1640995200),
             calculation_hash: "calc_hash_123".to_string(),
+            algorithm_version: KarmaAlgorithmVersion::V1,
         };
 
         assert_eq!(calculation.current_score, Uint128::new(150));
@@ -196,11 +200,49 @@ mod tests {
             rating_window: 86400, // 24 hours in seconds
             max_ratings_per_interaction: 1,
             rating_fee: Uint128::new(1),
+            algorithm: KarmaAlgorithmConfig::default(),
+            decay_half_life: DEFAULT_DECAY_HALF_LIFE,
+            history_retention: 0,
+            trust: TrustConfig::default(),
+            rating_grace_period: DEFAULT_RATING_GRACE_PERIOD,
+            min_karma_for_flagging: Uint128::new(DEFAULT_MIN_KARMA_FOR_FLAGGING),
+            feedback_flag_threshold: DEFAULT_FEEDBACK_FLAG_THRESHOLD,
+            arbitration: ArbitrationConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            enforcement: EnforcementConfig::default(),
+            rehabilitation: RehabilitationConfig::default(),
         };
 
         assert_eq!(config.min_karma_for_rating, Uint128::new(10));
         assert_eq!(config.rating_window, 86400);
         assert_eq!(config.max_ratings_per_interaction, 1);
+        assert_eq!(config.algorithm.version, KarmaAlgorithmVersion::V1);
+    }
+
+    /// Configs stored before algorithms were selectable fall back to V1 defaults
+    #[test]
+    fn test_karma_config_without_algorithm() {
+        let json = r#"{
+            "min_karma_for_rating": "10",
+            "min_karma_for_voting": "50",
+            "min_karma_for_proposal": "100",
+            "rating_window": 86400,
+            "max_ratings_per_interaction": 1,
+            "rating_fee": "1"
+        }"#;
+
+        let config: KarmaConfig = cosmwasm_std::from_json(json.as_bytes()).unwrap();
+        assert_eq!(config.algorithm, KarmaAlgorithmConfig::default());
+        assert_eq!(config.algorithm.params.max_karma_score, Uint128::new(10000));
+        assert_eq!(config.decay_half_life, DEFAULT_DECAY_HALF_LIFE);
+        assert_eq!(config.history_retention, 0);
+        assert_eq!(config.trust, TrustConfig::default());
+        assert_eq!(config.rating_grace_period, DEFAULT_RATING_GRACE_PERIOD);
+        assert_eq!(config.feedback_flag_threshold, DEFAULT_FEEDBACK_FLAG_THRESHOLD);
+        assert_eq!(config.arbitration, ArbitrationConfig::default());
+        assert_eq!(config.rate_limits, RateLimitConfig::default());
+        assert_eq!(config.enforcement, EnforcementConfig::default());
+        assert_eq!(config.rehabilitation, RehabilitationConfig::default());
     }
 
     /// Test validation functions
@@ -502,10 +544,10 @@ mod tests {
         fn test_karma_calculation_performance() {
             // Test karma calculation with many factors
             let factors = KarmaFactors {
-                average_rating: "8.5".to_string(),
+                average_rating: Decimal::percent(850),
                 rating_count: 1000,
                 interaction_frequency: Uint128::new(500),
-                time_decay: "0.98".to_string(),
+                time_decay: Decimal::percent(98),
                 external_factors: Some(Uint128::new(100)),
             };
 
@@ -516,6 +558,7 @@ mod tests {
                 factors,
                 last_updated: Timestamp::from_seconds(1640995200),
                 calculation_hash: "complex_calc_hash".to_string(),
+                algorithm_version: KarmaAlgorithmVersion::V2,
             };
 
             // Verify the calculation structure handles large numbers
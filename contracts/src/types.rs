@@ -3,7 +3,7 @@
 //! This module defines all the shared data structures used across
 //! the Agent-Karma smart contract ecosystem.
 
-use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cosmwasm_std::{Addr, Decimal, Int128, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -60,6 +60,28 @@ pub struct Rating {
     pub block_height: u64,
 }
 
+/// Public reply of the rated agent to a rating
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RatingReply {
+    /// Rating replied to
+    pub rating_id: String,
+    /// Reply text
+    pub text: String,
+    /// Timestamp when the reply was posted
+    pub replied_at: Timestamp,
+}
+
+/// Earlier version of an amended or retracted rating
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RatingRevision {
+    /// Score of this version
+    pub score: u8,
+    /// Feedback of this version
+    pub feedback: Option<String>,
+    /// Timestamp when this version was superseded
+    pub replaced_at: Timestamp,
+}
+
 /// Represents an interaction between agents
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Interaction {
@@ -103,23 +125,50 @@ pub struct KarmaCalculation {
     pub last_updated: Timestamp,
     /// Hash for verification purposes
     pub calculation_hash: String,
+    /// Version of the scoring algorithm that produced this calculation
+    #[serde(default)]
+    pub algorithm_version: KarmaAlgorithmVersion,
 }
 
 /// Factors used in karma calculation
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct KarmaFactors {
-    /// Average rating received
-    pub average_rating: String, // Using String to avoid floating point issues
+    /// Average rating received (exact fixed-point decimal)
+    pub average_rating: Decimal,
     /// Total number of ratings
     pub rating_count: u64,
     /// Interaction frequency score
     pub interaction_frequency: Uint128,
-    /// Time decay factor
-    pub time_decay: String,
+    /// Time decay factor (exact fixed-point decimal, 0 to 1)
+    pub time_decay: Decimal,
     /// External factors from oracles
     pub external_factors: Option<Uint128>,
 }
 
+/// One component of a karma score (base score, interaction bonus, ...)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KarmaComponent {
+    /// Component name
+    pub name: String,
+    /// Karma this component added (positive) or removed (negative)
+    pub contribution: Int128,
+    /// Individual rules that make up the component
+    pub modifiers: Vec<KarmaModifier>,
+}
+
+/// A single scoring rule and whether it applied
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KarmaModifier {
+    /// Modifier name
+    pub name: String,
+    /// Human-readable description of the rule
+    pub rule: String,
+    /// Whether the rule's condition was met
+    pub fired: bool,
+    /// Karma the rule added (positive) or removed (negative)
+    pub contribution: Int128,
+}
+
 /// Governance proposal
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Proposal {
@@ -192,6 +241,86 @@ pub struct OracleData {
     pub verified: bool,
 }
 
+/// Performance oracle payload, submitted as JSON with data type `performance`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PerformanceOracleData {
+    /// Agent the data describes; checked against the rated agent when present
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Overall performance score (0-100)
+    pub performance_score: u32,
+    /// Tasks completed in the reporting period
+    #[serde(default)]
+    pub tasks_completed: Option<u64>,
+    /// Share of time the agent was reachable
+    #[serde(default)]
+    pub uptime: Option<Decimal>,
+}
+
+/// Cross-chain reputation payload, submitted as JSON with data type `cross_chain`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CrossChainOracleData {
+    /// Agent the data describes; checked against the rated agent when present
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Reputation carried over from other chains (0-100)
+    pub reputation_score: u32,
+    /// Chains the reputation was aggregated from
+    #[serde(default)]
+    pub source_chains: Vec<String>,
+}
+
+/// Community sentiment payload, submitted as JSON with data type `sentiment`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SentimentOracleData {
+    /// Agent the data describes; checked against the rated agent when present
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    /// Aggregate sentiment score (0-100)
+    pub sentiment_score: u32,
+    /// Number of samples the score was computed from
+    #[serde(default)]
+    pub sample_size: Option<u64>,
+}
+
+/// Parsed oracle payload
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OraclePayload {
+    Performance(PerformanceOracleData),
+    CrossChain(CrossChainOracleData),
+    Sentiment(SentimentOracleData),
+}
+
+impl OraclePayload {
+    /// Data type the payload was submitted under
+    pub fn data_type(&self) -> &'static str {
+        match self {
+            OraclePayload::Performance(_) => "performance",
+            OraclePayload::CrossChain(_) => "cross_chain",
+            OraclePayload::Sentiment(_) => "sentiment",
+        }
+    }
+
+    /// Score (0-100) the payload contributes to external factors
+    pub fn score(&self) -> u32 {
+        match self {
+            OraclePayload::Performance(data) => data.performance_score,
+            OraclePayload::CrossChain(data) => data.reputation_score,
+            OraclePayload::Sentiment(data) => data.sentiment_score,
+        }
+    }
+
+    /// Agent the payload claims to describe, if any
+    pub fn agent_id(&self) -> Option<&str> {
+        match self {
+            OraclePayload::Performance(data) => data.agent_id.as_deref(),
+            OraclePayload::CrossChain(data) => data.agent_id.as_deref(),
+            OraclePayload::Sentiment(data) => data.agent_id.as_deref(),
+        }
+    }
+}
+
 /// Configuration for karma calculation parameters
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct KarmaConfig {
@@ -207,4 +336,411 @@ pub struct KarmaConfig {
     pub max_ratings_per_interaction: u8,
     /// Karma fee for rating submission
     pub rating_fee: Uint128,
-}
\ No newline at end of file
+    /// Scoring algorithm and its tunable parameters
+    #[serde(default)]
+    pub algorithm: KarmaAlgorithmConfig,
+    /// Half-life of karma for inactive agents (in seconds)
+    #[serde(default = "default_decay_half_life")]
+    pub decay_half_life: u64,
+    /// Age after which karma history entries are compacted into daily
+    /// summaries (in seconds, 0 keeps every entry)
+    #[serde(default)]
+    pub history_retention: u64,
+    /// Transitive rater trust computed over the rating graph
+    #[serde(default)]
+    pub trust: TrustConfig,
+    /// Time after submission during which a rater may amend or retract a
+    /// rating (in seconds, 0 makes ratings final)
+    #[serde(default = "default_rating_grace_period")]
+    pub rating_grace_period: u64,
+    /// Minimum karma required to flag rating feedback
+    #[serde(default = "default_min_karma_for_flagging")]
+    pub min_karma_for_flagging: Uint128,
+    /// Number of flags at which feedback is hidden from rating listings
+    #[serde(default = "default_feedback_flag_threshold")]
+    pub feedback_flag_threshold: u32,
+    /// Juror arbitration of compliance disputes
+    #[serde(default)]
+    pub arbitration: ArbitrationConfig,
+    /// Rolling earning cap and per-action rate limits
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    /// Actions taken automatically on abuse detection results
+    #[serde(default)]
+    pub enforcement: EnforcementConfig,
+    /// Expiry of compliance violations and recovery of their penalties
+    #[serde(default)]
+    pub rehabilitation: RehabilitationConfig,
+}
+
+/// Default karma half-life: 180 days in seconds
+pub const DEFAULT_DECAY_HALF_LIFE: u64 = 180 * 24 * 60 * 60;
+
+/// Default rating grace period: 1 hour in seconds
+pub const DEFAULT_RATING_GRACE_PERIOD: u64 = 60 * 60;
+
+/// Default minimum karma to flag feedback
+pub const DEFAULT_MIN_KARMA_FOR_FLAGGING: u128 = 50;
+
+/// Default number of flags that hides feedback
+pub const DEFAULT_FEEDBACK_FLAG_THRESHOLD: u32 = 3;
+
+fn default_decay_half_life() -> u64 {
+    DEFAULT_DECAY_HALF_LIFE
+}
+
+fn default_rating_grace_period() -> u64 {
+    DEFAULT_RATING_GRACE_PERIOD
+}
+
+fn default_min_karma_for_flagging() -> Uint128 {
+    Uint128::new(DEFAULT_MIN_KARMA_FOR_FLAGGING)
+}
+
+fn default_feedback_flag_threshold() -> u32 {
+    DEFAULT_FEEDBACK_FLAG_THRESHOLD
+}
+
+/// Parameters of the transitive (EigenTrust-style) rater trust computation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TrustConfig {
+    /// Agents trusted a priori; trust flows outwards from them. Cannot be
+    /// empty, or sock-puppets would start out as trusted as anyone else.
+    pub pre_trusted: Vec<String>,
+    /// Share of trust reset to the pre-trusted agents every epoch, strictly
+    /// between 0 and 1
+    pub damping: Decimal,
+}
+
+/// No agent is known in advance: karma-core pre-trusts its admin in place of
+/// the empty set
+impl Default for TrustConfig {
+    fn default() -> Self {
+        Self {
+            pre_trusted: vec![],
+            damping: Decimal::percent(15),
+        }
+    }
+}
+
+/// Parameters of juror arbitration of compliance disputes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArbitrationConfig {
+    /// Number of jurors drawn per dispute; odd, so that votes cannot tie
+    pub panel_size: u32,
+    /// Minimum karma for an agent to be drawn as a juror
+    pub min_juror_karma: Uint128,
+    /// Time after a dispute is opened during which jurors commit votes (in seconds)
+    pub commit_period: u64,
+    /// Time after the commit period during which jurors reveal votes (in seconds)
+    pub reveal_period: u64,
+    /// Karma earned by jurors who vote with the majority
+    pub juror_reward: Uint128,
+    /// Karma taken from jurors who do not reveal a vote
+    pub non_reveal_penalty: Uint128,
+}
+
+impl Default for ArbitrationConfig {
+    fn default() -> Self {
+        Self {
+            panel_size: 5,
+            min_juror_karma: Uint128::new(500),
+            commit_period: 2 * 24 * 60 * 60,
+            reveal_period: 24 * 60 * 60,
+            juror_reward: Uint128::new(10),
+            non_reveal_penalty: Uint128::new(20),
+        }
+    }
+}
+
+/// Most times an agent may perform an action within a sliding window
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ActionRateLimit {
+    /// Action type, such as "rating" or "interaction"
+    pub action: String,
+    /// Actions allowed per window, before the karma multiplier
+    pub max_actions: u32,
+    /// Window length (in seconds)
+    pub window: u64,
+}
+
+/// Sliding-window limits on karma earned and actions performed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RateLimitConfig {
+    /// Most karma an agent can earn over any 24 hours
+    pub daily_earning_cap: Uint128,
+    /// Limits of specific action types
+    pub actions: Vec<ActionRateLimit>,
+    /// Actions allowed per window for action types without their own limit
+    pub default_max_actions: u32,
+    /// Window of action types without their own limit (in seconds)
+    pub default_window: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let hourly = |action: &str, max_actions| ActionRateLimit {
+            action: action.to_string(),
+            max_actions,
+            window: 60 * 60,
+        };
+        Self {
+            daily_earning_cap: Uint128::new(100),
+            actions: vec![hourly("rating", 10), hourly("interaction", 50)],
+            default_max_actions: 20,
+            default_window: 60 * 60,
+        }
+    }
+}
+
+/// Action taken on an abuse detection result matching an enforcement policy
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementAction {
+    /// Record the violation without a penalty
+    Warn,
+    /// Record the violation and deduct the recommended penalty
+    Penalize,
+    /// Record the violation and bar the agent from rating for `duration` seconds
+    SuspendRating { duration: u64 },
+    /// Record the violation for the admin to act on
+    Escalate,
+}
+
+/// Maps a violation type detected with enough confidence to an action
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EnforcementPolicy {
+    /// Violation type, as accepted by `ApplyCompliancePenalty` (e.g. "spam_rating")
+    pub violation_type: String,
+    /// Lowest detection confidence the policy applies to
+    pub min_confidence: Decimal,
+    pub action: EnforcementAction,
+}
+
+/// Automatic enforcement of abuse detection results
+///
+/// When several policies match a result, the one with the highest
+/// `min_confidence` applies.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EnforcementConfig {
+    pub policies: Vec<EnforcementPolicy>,
+    /// Time after an enforcement during which the same violation type is not
+    /// enforced against the agent again (in seconds); must not be zero
+    pub cooldown: u64,
+}
+
+impl Default for EnforcementConfig {
+    fn default() -> Self {
+        let penalize = |violation_type: &str| EnforcementPolicy {
+            violation_type: violation_type.to_string(),
+            min_confidence: Decimal::percent(50),
+            action: EnforcementAction::Penalize,
+        };
+        Self {
+            policies: vec![
+                penalize("spam_rating"),
+                penalize("bot_behavior"),
+                penalize("rating_manipulation"),
+                penalize("collusion_ring"),
+            ],
+            cooldown: 24 * 60 * 60,
+        }
+    }
+}
+
+/// Time a type of violation stays active on an agent's record
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ViolationExpiry {
+    /// Violation type, as accepted by `ApplyCompliancePenalty` (e.g. "spam_rating")
+    pub violation_type: String,
+    /// Time after the violation at which it expires (in seconds)
+    pub window: u64,
+}
+
+/// Expiry of compliance violations and recovery of their penalties
+///
+/// Once an agent has gone `probation_period` without a new violation, it
+/// recovers `recovery_percent` of every penalty it kept, linearly over
+/// `recovery_period`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RehabilitationConfig {
+    /// Expiry windows of specific violation types
+    pub expiry_windows: Vec<ViolationExpiry>,
+    /// Expiry window of violation types without their own (in seconds)
+    pub default_expiry: u64,
+    /// Time without a new violation before penalties start being recovered (in seconds)
+    pub probation_period: u64,
+    /// Share of each penalty that can be recovered, in percent
+    pub recovery_percent: u64,
+    /// Time over which the recoverable share is returned (in seconds)
+    pub recovery_period: u64,
+}
+
+impl Default for RehabilitationConfig {
+    fn default() -> Self {
+        Self {
+            expiry_windows: vec![ViolationExpiry {
+                violation_type: "collusion_ring".to_string(),
+                window: 180 * 24 * 60 * 60,
+            }],
+            default_expiry: 90 * 24 * 60 * 60,
+            probation_period: 30 * 24 * 60 * 60,
+            recovery_percent: 50,
+            recovery_period: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Bucket width used when downsampling karma history
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryInterval {
+    Daily,
+    Weekly,
+}
+
+impl HistoryInterval {
+    /// Bucket width in seconds
+    pub fn seconds(&self) -> u64 {
+        match self {
+            HistoryInterval::Daily => 24 * 60 * 60,
+            HistoryInterval::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Why an agent's karma moved
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KarmaLedgerReason {
+    /// Karma held when the ledger was opened
+    OpeningBalance,
+    /// Fee charged for submitting a rating
+    RatingFee,
+    /// Rating fee returned on retraction
+    RatingFeeRefund,
+    /// Karma earned from a positive rating
+    RatingReward,
+    /// Karma lost to a poor rating
+    RatingPenalty,
+    /// Rating reward taken back when the rating was amended or retracted
+    RatingRewardReversal,
+    /// Rating penalty given back when the rating was amended or retracted
+    RatingPenaltyReversal,
+    /// Score recomputed from ratings, decay and external signals
+    Recalculation,
+    /// Decay settled when the agent's karma was frozen
+    Decay,
+    /// Penalty for a compliance violation
+    CompliancePenalty,
+    /// Stake put up to dispute a violation
+    DisputeStake,
+    /// Dispute stake given back to the challenger
+    DisputeStakeReturn,
+    /// Reward paid to a successful challenger
+    DisputeReward,
+    /// Compliance penalty given back after a dispute
+    PenaltyRefund,
+    /// Reward for voting with the majority of a dispute's jurors
+    JurorReward,
+    /// Penalty for a juror who never revealed a vote
+    JurorPenalty,
+    /// Compliance penalty recovered after a probation period in good standing
+    PenaltyRecovery,
+}
+
+/// One movement of an agent's karma
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KarmaLedgerEntry {
+    /// Position in the agent's ledger, starting at zero
+    pub sequence: u64,
+    pub reason: KarmaLedgerReason,
+    /// Karma added
+    pub credit: Uint128,
+    /// Karma removed
+    pub debit: Uint128,
+    /// Karma held after the movement
+    pub balance: Uint128,
+    /// Other agent involved, such as the rater or the challenger
+    pub counterparty: Option<Addr>,
+    /// Rating, violation or dispute the movement relates to
+    pub related_id: Option<String>,
+    pub block_height: u64,
+    pub timestamp: Timestamp,
+}
+
+/// Karma scores recorded within one history bucket
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KarmaHistoryBucket {
+    /// Start of the bucket
+    pub start: Timestamp,
+    /// Lowest score recorded in the bucket
+    pub min: Uint128,
+    /// Highest score recorded in the bucket
+    pub max: Uint128,
+    /// Last score recorded in the bucket
+    pub close: Uint128,
+    /// Number of calculations summarised
+    pub samples: u64,
+}
+
+/// Version of the karma scoring algorithm
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum KarmaAlgorithmVersion {
+    /// Quadratic base score, extreme ratings carry reduced weight
+    #[default]
+    V1,
+    /// Linear base score, every rating carries the same weight
+    V2,
+}
+
+/// Selected karma scoring algorithm together with its parameters
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct KarmaAlgorithmConfig {
+    /// Algorithm version used for new calculations
+    pub version: KarmaAlgorithmVersion,
+    /// Weights and thresholds fed to the algorithm
+    pub params: KarmaAlgorithmParams,
+}
+
+/// Tunable weights of the karma scoring algorithm
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KarmaAlgorithmParams {
+    /// Karma per rating at a perfect average
+    pub base_rating_weight: Uint128,
+    /// Karma per interaction before diminishing returns
+    pub interaction_bonus_weight: Uint128,
+    /// Bonus per rating received from a high-karma rater
+    pub high_karma_interaction_bonus: Uint128,
+    /// Penalty per poor rating (doubled for very poor ratings)
+    pub low_karma_interaction_penalty: Uint128,
+    /// Number of high ratings required for the consistency bonus
+    pub consistency_bonus_threshold: u64,
+    /// Consistency bonus (doubled again for excellence)
+    pub consistency_bonus_value: Uint128,
+    /// Weight of oracle performance metrics (percentage)
+    pub performance_weight: u8,
+    /// Weight of oracle cross-chain reputation (percentage)
+    pub cross_chain_weight: u8,
+    /// Weight of oracle sentiment data (percentage)
+    pub sentiment_weight: u8,
+    /// Maximum karma score an agent can reach
+    pub max_karma_score: Uint128,
+}
+
+impl Default for KarmaAlgorithmParams {
+    fn default() -> Self {
+        Self {
+            base_rating_weight: Uint128::new(100),
+            interaction_bonus_weight: Uint128::new(10),
+            high_karma_interaction_bonus: Uint128::new(20),
+            low_karma_interaction_penalty: Uint128::new(5),
+            consistency_bonus_threshold: 10,
+            consistency_bonus_value: Uint128::new(50),
+            performance_weight: 15,
+            cross_chain_weight: 10,
+            sentiment_weight: 5,
+            max_karma_score: Uint128::new(10000),
+        }
+    }
+}